@@ -1,42 +1,65 @@
 use crate::game::assets::GameAssets;
 use crate::menu::assets::MenuAssets;
+use crate::theme::Theme;
 
-use sdl2::pixels::Color;
+use std::collections::HashMap;
+
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::surface::Surface;
 use sdl2::ttf::{Sdl2TtfContext, Font};
 use sdl2::video::WindowContext;
-use sdl2::render::{TextureCreator, Texture};
+use sdl2::render::{BlendMode, TextureCreator, Texture, WindowCanvas};
 
 pub struct Assets<'a, 'b> {
     texture_creator: &'a TextureCreator<WindowContext>,
     ttf_context: &'a Sdl2TtfContext,
+    theme: Theme,
 
-    game_assets: Option<GameAssets<'a, 'b>>,
+    game_assets: Option<(String, GameAssets<'a, 'b>)>, // Only the assets for the most recent gamemode are kept
     menu_assets: Option<MenuAssets<'a>>,
+    render_targets: Vec<Texture<'a>>, // Screen sized textures for scenes that show more than one game
 }
 
 impl<'a, 'b> Assets<'a, 'b> {
-    pub fn new(texture_creator: &'a TextureCreator<WindowContext>, ttf_context: &'a Sdl2TtfContext) -> Result<Self, String> {
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>, ttf_context: &'a Sdl2TtfContext, theme: Theme) -> Result<Self, String> {
         Ok(Self {
             texture_creator,
             ttf_context,
+            theme,
 
             game_assets: None,
             menu_assets: None,
-
+            render_targets: Vec::new(),
         })
     }
 
-    // Load assets if they are not currently loaded, then return them
-    pub fn get_game_assets(&mut self, gamemode_name: &str) -> Result<&mut GameAssets<'a, 'b>, String> {
-        if self.game_assets.is_none() {
-            self.game_assets = Some(GameAssets::new(self.texture_creator, self.ttf_context, &gamemode_name.to_uppercase())?);
+    // Load assets if they are not currently loaded for this gamemode, then return them
+    pub fn get_game_assets(&mut self, gamemode_name: &str, background: &Option<String>) -> Result<&mut GameAssets<'a, 'b>, String> {
+        let is_loaded = matches!(&self.game_assets, Some((name, _)) if name == gamemode_name);
+        if !is_loaded {
+            // Release the previous gamemode's assets before loading the new ones
+            self.game_assets = None;
+            let assets = GameAssets::new(self.texture_creator, self.ttf_context, &self.theme, &gamemode_name.to_uppercase(), background)?;
+            self.game_assets = Some((gamemode_name.to_string(), assets));
+        }
+        Ok(&mut self.game_assets.as_mut().unwrap().1)
+    }
+
+    /* Game assets along with a texture to draw that game into, one texture for each index */
+    pub fn get_game_assets_and_target(&mut self, gamemode_name: &str, background: &Option<String>, index: usize) -> Result<(&mut GameAssets<'a, 'b>, &mut Texture<'a>), String> {
+        self.get_game_assets(gamemode_name, background)?;
+        while self.render_targets.len() <= index {
+            let target = self.texture_creator.create_texture_target(None, crate::display::LOGICAL_WIDTH, crate::display::LOGICAL_HEIGHT)
+                .map_err(|e| e.to_string())?;
+            self.render_targets.push(target);
         }
-        Ok(self.game_assets.as_mut().unwrap())
+        Ok((&mut self.game_assets.as_mut().unwrap().1, &mut self.render_targets[index]))
     }
 
     pub fn get_menu_assets(&mut self) -> Result<&mut MenuAssets<'a>, String> {
         if self.menu_assets.is_none() {
-            self.menu_assets = Some(MenuAssets::new(self.texture_creator, self.ttf_context)?);
+            self.menu_assets = Some(MenuAssets::new(self.texture_creator, self.ttf_context, &self.theme)?);
         }
         Ok(self.menu_assets.as_mut().unwrap())
     }
@@ -50,3 +73,88 @@ pub fn create_text_texture<'a, T>(text: &str, color: Color, font: &Font, texture
     texture_creator.create_texture_from_surface(&surface)
         .map_err(|e| e.to_string())
 }
+
+/* Pre-rendered characters, used to draw text that changes often (like stats) without rendering it every frame */
+pub struct GlyphAtlas<'a> {
+    texture: Texture<'a>,
+    glyphs: HashMap<char, Rect>,
+}
+
+impl<'a> GlyphAtlas<'a> {
+    pub fn new(characters: &str, color: Color, font: &Font, texture_creator: &'a TextureCreator<WindowContext>) -> Result<Self, String> {
+        let mut glyph_surfaces = Vec::new();
+        for c in characters.chars() {
+            let mut surface = font
+                .render(&c.to_string())
+                .blended(color)
+                .map_err(|e| e.to_string())?;
+            // Copy glyphs exactly instead of blending them with the empty atlas
+            surface.set_blend_mode(BlendMode::None)?;
+            glyph_surfaces.push((c, surface));
+        }
+
+        let width = glyph_surfaces.iter().map(|(_, x)| x.width()).sum::<u32>().max(1);
+        let height = glyph_surfaces.iter().map(|(_, x)| x.height()).max().unwrap_or(1);
+        let mut atlas = Surface::new(width, height, PixelFormatEnum::RGBA32)?;
+        let mut glyphs = HashMap::new();
+        let mut x = 0;
+        for (c, surface) in glyph_surfaces.iter() {
+            let region = Rect::new(x, 0, surface.width(), surface.height());
+            surface.blit(None, &mut atlas, region)?;
+            glyphs.insert(*c, region);
+            x += surface.width() as i32;
+        }
+
+        let mut texture = texture_creator.create_texture_from_surface(&atlas)
+            .map_err(|e| e.to_string())?;
+        texture.set_blend_mode(BlendMode::Blend);
+        Ok(Self {
+            texture,
+            glyphs,
+        })
+    }
+
+    pub fn size_of(&self, text: &str) -> (u32, u32) {
+        text.chars()
+            .filter_map(|c| self.glyphs.get(&c))
+            .fold((0, 0), |(width, height), glyph| (width + glyph.width(), height.max(glyph.height())))
+    }
+
+    pub fn set_color_mod(&mut self, color: Color) {
+        self.texture.set_color_mod(color.r, color.g, color.b);
+    }
+
+    pub fn draw(&self, canvas: &mut WindowCanvas, text: &str, x: i32, y: i32) -> Result<(), String> {
+        let mut offset = x;
+        for c in text.chars() {
+            let glyph = self.glyphs.get(&c)
+                .ok_or_else(|| format!("No glyph for '{}' in glyph atlas", c))?;
+            canvas.copy(&self.texture, *glyph, Rect::new(offset, y, glyph.width(), glyph.height()))?;
+            offset += glyph.width() as i32;
+        }
+        Ok(())
+    }
+}
+
+/* A text texture that is only rendered again when the text changes */
+pub struct CachedText<'a> {
+    text: String,
+    texture: Option<Texture<'a>>,
+}
+
+impl<'a> CachedText<'a> {
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+            texture: None,
+        }
+    }
+
+    pub fn get(&mut self, text: &str, color: Color, font: &Font, texture_creator: &'a TextureCreator<WindowContext>) -> Result<&Texture<'a>, String> {
+        if self.texture.is_none() || self.text != text {
+            self.texture = Some(create_text_texture(text, color, font, texture_creator)?);
+            self.text = text.to_string();
+        }
+        Ok(self.texture.as_ref().unwrap())
+    }
+}
@@ -0,0 +1,362 @@
+use crate::game::GameEvent;
+
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+use sdl2::Sdl;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Sound {
+    Move,
+    Rotate,
+    Kick,
+    Hold,
+    SoftDrop,
+    HardDrop,
+    Lock,
+    Single,
+    Double,
+    Triple,
+    Quad,
+    Spin,
+    Combo,
+    LevelUp,
+    GameOver,
+    Fault,
+    Garbage,
+    MenuMove,
+    MenuSelect,
+    MenuBack,
+}
+
+/* Sounds without a sample are silent. Music tracks are named so that gamemodes can refer to them */
+#[derive(Deserialize)]
+pub struct SoundConfig {
+    pub muted: bool,
+    pub volume: f64, // 0.0 to 1.0
+    pub sounds: HashMap<Sound, String>,
+    pub music_muted: bool,
+    pub music_volume: f64, // 0.0 to 1.0
+    pub crossfade: u32, // Milliseconds to fade between tracks
+    pub menu_music: Option<String>,
+    pub music: HashMap<String, String>,
+}
+
+impl SoundConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.volume) {
+            return Err(format!("Sound config has invalid volume: {}, must be between 0.0 and 1.0", self.volume));
+        }
+        if !(0.0..=1.0).contains(&self.music_volume) {
+            return Err(format!("Sound config has invalid music_volume: {}, must be between 0.0 and 1.0", self.music_volume));
+        }
+        for (sound, path) in self.sounds.iter() {
+            if !Path::new(path).is_file() {
+                return Err(format!("Sound config refers to {} for {:?}, but that file was not found", path, sound));
+            }
+        }
+        for (track, path) in self.music.iter() {
+            if !Path::new(path).is_file() {
+                return Err(format!("Sound config refers to {} for music track {}, but that file was not found", path, track));
+            }
+        }
+        if let Some(track) = &self.menu_music {
+            if !self.music.contains_key(track) {
+                return Err(format!("Sound config has menu_music {}, but there is no music track with that name", track));
+            }
+        }
+        Ok(())
+    }
+}
+
+/* Something that can play sounds, so the rest of the game does not depend on having a sound device.
+   Music plays in two slots so that one track can fade in while the other fades out */
+pub trait AudioBackend {
+    fn play(&mut self, sound: Sound);
+    fn set_volume(&mut self, volume: f64);
+    fn start_music(&mut self, slot: usize, track: &str);
+    fn stop_music(&mut self, slot: usize);
+    fn set_music_volume(&mut self, slot: usize, volume: f64);
+    fn pause_music(&mut self, paused: bool);
+}
+
+/* Plays nothing, used for builds without sound and when no sound device can be opened */
+pub struct NullBackend;
+
+impl AudioBackend for NullBackend {
+    fn play(&mut self, _sound: Sound) {}
+    fn set_volume(&mut self, _volume: f64) {}
+    fn start_music(&mut self, _slot: usize, _track: &str) {}
+    fn stop_music(&mut self, _slot: usize) {}
+    fn set_music_volume(&mut self, _slot: usize, _volume: f64) {}
+    fn pause_music(&mut self, _paused: bool) {}
+}
+
+/* Music is loaded as chunks rather than mixer::Music, since only one Music can play at a time and crossfades need two */
+#[cfg(feature = "sound")]
+pub struct MixerBackend {
+    _audio_subsystem: sdl2::AudioSubsystem,
+    _mixer_context: Option<sdl2::mixer::Sdl2MixerContext>,
+    chunks: HashMap<Sound, sdl2::mixer::Chunk>,
+    music: HashMap<String, sdl2::mixer::Chunk>,
+}
+
+#[cfg(feature = "sound")]
+impl MixerBackend {
+    const CHANNELS: i32 = 16;
+    const MUSIC_CHANNELS: i32 = 2; // Reserved so sounds never interrupt the music, one for each slot
+
+    pub fn new(sdl_context: &Sdl, config: &SoundConfig) -> Result<Self, String> {
+        use sdl2::mixer;
+
+        let audio_subsystem = sdl_context.audio()?;
+        // Only needed for compressed formats, wav files load without it
+        let mixer_context = mixer::init(mixer::InitFlag::OGG).ok();
+        mixer::open_audio(44_100, mixer::DEFAULT_FORMAT, mixer::DEFAULT_CHANNELS, 512)?;
+        mixer::allocate_channels(Self::CHANNELS);
+        mixer::reserve_channels(Self::MUSIC_CHANNELS);
+
+        let mut chunks = HashMap::new();
+        for (sound, path) in config.sounds.iter() {
+            let chunk = mixer::Chunk::from_file(path)
+                .map_err(|e| format!("Could not load sound {}: {}", path, e))?;
+            chunks.insert(*sound, chunk);
+        }
+        let mut music = HashMap::new();
+        for (track, path) in config.music.iter() {
+            let chunk = mixer::Chunk::from_file(path)
+                .map_err(|e| format!("Could not load music {}: {}", path, e))?;
+            music.insert(track.to_string(), chunk);
+        }
+
+        let mut backend = Self {
+            _audio_subsystem: audio_subsystem,
+            _mixer_context: mixer_context,
+            chunks,
+            music,
+        };
+        backend.set_volume(config.volume);
+        Ok(backend)
+    }
+}
+
+#[cfg(feature = "sound")]
+impl AudioBackend for MixerBackend {
+    fn play(&mut self, sound: Sound) {
+        if let Some(chunk) = self.chunks.get(&sound) {
+            // Failing to play means every channel is busy, so the sound is skipped
+            let _ = sdl2::mixer::Channel::all().play(chunk, 0);
+        }
+    }
+
+    fn set_volume(&mut self, volume: f64) {
+        for channel in Self::MUSIC_CHANNELS..Self::CHANNELS {
+            sdl2::mixer::Channel(channel).set_volume((volume * sdl2::mixer::MAX_VOLUME as f64) as i32);
+        }
+    }
+
+    fn start_music(&mut self, slot: usize, track: &str) {
+        if let Some(chunk) = self.music.get(track) {
+            let channel = sdl2::mixer::Channel(slot as i32);
+            channel.set_volume(0);
+            let _ = channel.play(chunk, -1);
+        }
+    }
+
+    fn stop_music(&mut self, slot: usize) {
+        sdl2::mixer::Channel(slot as i32).halt();
+    }
+
+    fn set_music_volume(&mut self, slot: usize, volume: f64) {
+        sdl2::mixer::Channel(slot as i32).set_volume((volume * sdl2::mixer::MAX_VOLUME as f64) as i32);
+    }
+
+    fn pause_music(&mut self, paused: bool) {
+        for channel in 0..Self::MUSIC_CHANNELS {
+            if paused {
+                sdl2::mixer::Channel(channel).pause();
+            } else {
+                sdl2::mixer::Channel(channel).resume();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sound")]
+impl Drop for MixerBackend {
+    fn drop(&mut self) {
+        // Chunks have to be freed before the device is closed
+        sdl2::mixer::Channel::all().halt();
+        self.chunks.clear();
+        self.music.clear();
+        sdl2::mixer::close_audio();
+    }
+}
+
+pub struct Audio {
+    backend: Box<dyn AudioBackend>,
+    muted: bool,
+
+    music_volume: f64, // Zero when music is muted
+    crossfade: u128, // µs
+    menu_music: Option<String>,
+    track: Option<String>, // Track that is playing or fading in
+    slot: usize, // Music slot of the track, the other slot is fading out
+    fade_timer: u128,
+    music_paused: bool,
+}
+
+impl Audio {
+    /* Falls back to the null backend if no sound device is available */
+    pub fn new(sdl_context: &Sdl) -> Result<Self, String> {
+        let config = load_config()?;
+        let backend = open_backend(sdl_context, &config);
+        Ok(Self {
+            backend,
+            muted: config.muted,
+
+            music_volume: music_volume(&config),
+            crossfade: config.crossfade as u128 * 1000,
+            menu_music: config.menu_music,
+            track: None,
+            slot: 0,
+            fade_timer: 0,
+            music_paused: false,
+        })
+    }
+
+    /* Fade the music, called once per simulation step */
+    pub fn update(&mut self, elapsed: u128) {
+        if self.music_paused || self.fade_timer >= self.crossfade {
+            return;
+        }
+        self.fade_timer += elapsed;
+        self.apply_music_volume();
+        if self.fade_timer >= self.crossfade {
+            self.backend.stop_music(1 - self.slot);
+        }
+    }
+
+    /* Crossfade to a track, nothing happens if it is already playing */
+    pub fn play_music(&mut self, track: &str) {
+        if self.track.as_deref() == Some(track) {
+            return;
+        }
+        // A fade that was still going is cut short, so at most two tracks are ever playing
+        self.backend.stop_music(1 - self.slot);
+        self.slot = 1 - self.slot;
+        self.backend.start_music(self.slot, track);
+        self.track = Some(track.to_string());
+        self.fade_timer = 0;
+        self.apply_music_volume();
+    }
+
+    pub fn play_menu_music(&mut self) {
+        if let Some(track) = self.menu_music.clone() {
+            self.play_music(&track);
+        }
+    }
+
+    pub fn pause_music(&mut self, paused: bool) {
+        if self.music_paused != paused {
+            self.music_paused = paused;
+            self.backend.pause_music(paused);
+        }
+    }
+
+    fn apply_music_volume(&mut self) {
+        let fade = if self.crossfade == 0 { 1.0 } else { f64::min(self.fade_timer as f64 / self.crossfade as f64, 1.0) };
+        self.backend.set_music_volume(self.slot, self.music_volume * fade);
+        self.backend.set_music_volume(1 - self.slot, self.music_volume * (1.0 - fade));
+    }
+
+    pub fn play(&mut self, sound: Sound) {
+        if !self.muted {
+            self.backend.play(sound);
+        }
+    }
+
+    pub fn play_game_events(&mut self, events: &[GameEvent]) {
+        for event in events {
+            if let GameEvent::MusicChanged(track) = event {
+                self.play_music(track);
+            }
+            for sound in event_sounds(event) {
+                self.play(sound);
+            }
+        }
+    }
+
+    /* Apply changes to the volume and mute settings, changing the samples or tracks needs a restart */
+    pub fn reload_config(&mut self) -> Result<(), String> {
+        let config = load_config()?;
+        self.backend.set_volume(config.volume);
+        self.muted = config.muted;
+        self.music_volume = music_volume(&config);
+        self.crossfade = config.crossfade as u128 * 1000;
+        self.apply_music_volume();
+        Ok(())
+    }
+}
+
+fn music_volume(config: &SoundConfig) -> f64 {
+    if config.music_muted {
+        0.0
+    } else {
+        config.music_volume
+    }
+}
+
+fn load_config() -> Result<SoundConfig, String> {
+    let config: SoundConfig = crate::load_data_ron(Path::new("config/sound.ron"))?;
+    config.validate()?;
+    Ok(config)
+}
+
+#[cfg(feature = "sound")]
+fn open_backend(sdl_context: &Sdl, config: &SoundConfig) -> Box<dyn AudioBackend> {
+    match MixerBackend::new(sdl_context, config) {
+        Ok(backend) => Box::new(backend),
+        Err(e) => {
+            eprintln!("Sound is disabled: {}", e);
+            Box::new(NullBackend)
+        }
+    }
+}
+
+#[cfg(not(feature = "sound"))]
+fn open_backend(_sdl_context: &Sdl, _config: &SoundConfig) -> Box<dyn AudioBackend> {
+    Box::new(NullBackend)
+}
+
+fn event_sounds(event: &GameEvent) -> Vec<Sound> {
+    match event {
+        GameEvent::Moved => vec![Sound::Move],
+        GameEvent::Rotated { kick: 0 } => vec![Sound::Rotate],
+        GameEvent::Rotated { .. } => vec![Sound::Kick],
+        GameEvent::Held => vec![Sound::Hold],
+        GameEvent::SoftDropped => vec![Sound::SoftDrop],
+        GameEvent::HardDropped => vec![Sound::HardDrop],
+        GameEvent::Locked => vec![Sound::Lock],
+        GameEvent::LinesCleared(clear) => {
+            let mut sounds = vec![match clear.rows.len() {
+                1 => Sound::Single,
+                2 => Sound::Double,
+                3 => Sound::Triple,
+                _ => Sound::Quad,
+            }];
+            if clear.spin {
+                sounds.push(Sound::Spin);
+            }
+            if clear.combo > 0 {
+                sounds.push(Sound::Combo);
+            }
+            sounds
+        }
+        GameEvent::LevelUp => vec![Sound::LevelUp],
+        GameEvent::PieceSpawned | GameEvent::RulesetChanged | GameEvent::MusicChanged(_) => Vec::new(),
+        GameEvent::GarbageReceived(_) => vec![Sound::Garbage],
+        GameEvent::FinesseFault => vec![Sound::Fault],
+        GameEvent::GameEnded => vec![Sound::GameOver],
+    }
+}
@@ -0,0 +1,187 @@
+/* A simple bot that speaks the Tetris Bot Protocol, used to try out the bot adapter.
+   It drops each piece wherever it lands lowest, without holding or spinning */
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+const WIDTH: i32 = 10;
+const HEIGHT: i32 = 40;
+const ORIENTATIONS: [&str; 4] = ["north", "east", "south", "west"];
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum FrontendMessage {
+    Rules,
+    Start {
+        hold: Option<String>,
+        queue: Vec<String>,
+        board: Vec<Vec<Option<String>>>,
+    },
+    Stop,
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        piece_move: Move,
+    },
+    NewPiece {
+        piece: String,
+    },
+    Quit,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+        features: Vec<String>,
+    },
+    Ready,
+    Suggestion {
+        moves: Vec<Move>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Move {
+    location: PieceLocation,
+    spin: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct PieceLocation {
+    #[serde(rename = "type")]
+    piece: String,
+    orientation: String,
+    x: i32,
+    y: i32,
+}
+
+struct State {
+    board: Vec<Vec<bool>>, // Rows from the bottom up
+    queue: Vec<String>,
+    hold: Option<String>,
+}
+
+fn main() {
+    let stdin = std::io::stdin();
+    let mut state: Option<State> = None;
+    send(&BotMessage::Info {
+        name: String::from("Dummy"),
+        version: String::from(env!("CARGO_PKG_VERSION")),
+        author: String::from("idk"),
+        features: Vec::new(),
+    });
+
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(x) => x,
+            Err(_) => break,
+        };
+        let message = match serde_json::from_str::<FrontendMessage>(&line) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        match message {
+            FrontendMessage::Rules => send(&BotMessage::Ready),
+            FrontendMessage::Start { hold, queue, board } => {
+                let board = board.iter().map(|row| row.iter().map(|x| x.is_some()).collect()).collect();
+                state = Some(State { board, queue, hold });
+            }
+            FrontendMessage::Stop => state = None,
+            FrontendMessage::Suggest => {
+                if let Some(state) = &state {
+                    send(&BotMessage::Suggestion { moves: suggest(state) });
+                }
+            }
+            FrontendMessage::Play { piece_move } => {
+                if let Some(state) = &mut state {
+                    play(state, &piece_move);
+                }
+            }
+            FrontendMessage::NewPiece { piece } => {
+                if let Some(state) = &mut state {
+                    state.queue.push(piece);
+                }
+            }
+            FrontendMessage::Quit => break,
+        }
+    }
+}
+
+fn send(message: &BotMessage) {
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "{}", serde_json::to_string(message).unwrap()).unwrap();
+    stdout.flush().unwrap();
+}
+
+/* Every placement of the current piece, lowest first */
+fn suggest(state: &State) -> Vec<Move> {
+    let piece = match state.queue.first() {
+        Some(x) => x,
+        None => return Vec::new(),
+    };
+    let mut moves = Vec::new();
+    for orientation in ORIENTATIONS {
+        for x in 0..WIDTH {
+            // Find the lowest y the piece can rest at when dropped from the top
+            let mut y = HEIGHT - 3;
+            if !fits(&state.board, piece, orientation, x, y) {
+                continue;
+            }
+            while fits(&state.board, piece, orientation, x, y - 1) {
+                y -= 1;
+            }
+            let top = cells(piece, orientation, x, y).iter().map(|(_, y)| *y).max().unwrap();
+            let location = PieceLocation { piece: piece.clone(), orientation: String::from(orientation), x, y };
+            moves.push((top, Move { location, spin: String::from("none") }));
+        }
+    }
+    moves.sort_by_key(|(top, _)| *top);
+    moves.into_iter().map(|(_, x)| x).collect()
+}
+
+fn play(state: &mut State, piece_move: &Move) {
+    let location = &piece_move.location;
+    for (x, y) in cells(&location.piece, &location.orientation, location.x, location.y) {
+        state.board[y as usize][x as usize] = true;
+    }
+    state.board.retain(|row| !row.iter().all(|x| *x));
+    state.board.resize(HEIGHT as usize, vec![false; WIDTH as usize]);
+
+    if state.queue.is_empty() {
+        return;
+    }
+    let current = state.queue.remove(0);
+    if current != location.piece && state.hold.replace(current).is_none() && !state.queue.is_empty() {
+        state.queue.remove(0);
+    }
+}
+
+fn fits(board: &[Vec<bool>], piece: &str, orientation: &str, x: i32, y: i32) -> bool {
+    cells(piece, orientation, x, y).iter()
+        .all(|(x, y)| *x >= 0 && *x < WIDTH && *y >= 0 && *y < HEIGHT && !board[*y as usize][*x as usize])
+}
+
+fn cells(piece: &str, orientation: &str, x: i32, y: i32) -> Vec<(i32, i32)> {
+    let north: [(i32, i32); 4] = match piece {
+        "I" => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+        "O" => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        "T" => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+        "L" => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+        "J" => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+        "S" => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+        _ => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+    };
+    north.iter()
+        .map(|(dx, dy)| match orientation {
+            "east" => (*dy, -*dx),
+            "south" => (-*dx, -*dy),
+            "west" => (-*dy, *dx),
+            _ => (*dx, *dy),
+        })
+        .map(|(dx, dy)| (x + dx, y + dy))
+        .collect()
+}
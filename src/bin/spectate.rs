@@ -0,0 +1,94 @@
+/* Watch a game from the terminal by connecting to its spectator stream.
+   Usage: spectate [address], the address defaults to the one in the default config/spectator.ron */
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+use serde::Deserialize;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:7879";
+
+#[derive(Deserialize)]
+struct Snapshot {
+    matrix: Vec<Vec<String>>,
+    offscreen_rows: usize,
+    piece: PieceSnapshot,
+    hold: Option<String>,
+    queue: Vec<String>,
+    level: usize,
+    stats: Stats,
+    game_over: bool,
+}
+
+#[derive(Deserialize)]
+struct PieceSnapshot {
+    ghost_row: i32,
+    row: i32,
+    cells: Vec<(i32, i32)>,
+}
+
+#[derive(Deserialize)]
+struct Stats {
+    score: u32,
+    time: u128,
+    lines_cleared: u32,
+    pieces_placed: u32,
+}
+
+fn main() {
+    let address = std::env::args().nth(1).unwrap_or_else(|| String::from(DEFAULT_ADDRESS));
+    let stream = match TcpStream::connect(&address) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Could not connect to {}: {}", address, e);
+            return;
+        }
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(x) => x,
+            Err(_) => break,
+        };
+        match serde_json::from_str::<Snapshot>(&line) {
+            Ok(snapshot) => draw(&snapshot),
+            Err(e) => eprintln!("Ignoring snapshot: {}", e),
+        }
+    }
+    println!("The game closed the stream");
+}
+
+/* Blocks are #, the current piece is @ and its ghost is + */
+fn draw(snapshot: &Snapshot) {
+    let piece = &snapshot.piece;
+    let drop_distance = piece.ghost_row - piece.row;
+    let mut text = String::from("\x1b[H\x1b[2J");
+    for (row, blocks) in snapshot.matrix.iter().enumerate().skip(snapshot.offscreen_rows) {
+        text.push('|');
+        for (col, block) in blocks.iter().enumerate() {
+            let cell = (col as i32, row as i32);
+            let symbol = if piece.cells.contains(&cell) {
+                "@"
+            } else if piece.cells.iter().any(|(x, y)| (*x, y + drop_distance) == cell) {
+                "+"
+            } else if block != "Empty" {
+                "#"
+            } else {
+                " "
+            };
+            text.push_str(symbol);
+        }
+        text.push_str("|\n");
+    }
+
+    let stats = &snapshot.stats;
+    text.push_str(&format!("Hold: {}  Next: {}\n", snapshot.hold.as_deref().unwrap_or("-"), snapshot.queue.join(" ")));
+    text.push_str(&format!("Level {}  Score {}  Lines {}  Pieces {}  Time {:.2}s\n",
+        snapshot.level, stats.score, stats.lines_cleared, stats.pieces_placed, stats.time as f64 / 1_000_000.0));
+    if snapshot.game_over {
+        text.push_str("Game over\n");
+    }
+
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(text.as_bytes());
+    let _ = stdout.flush();
+}
@@ -0,0 +1,45 @@
+/* A built-in player for demos, which places each piece wherever the heuristic scores best */
+use super::taps;
+use crate::game::{Game, GameEvent, search::Heuristic};
+use crate::input::{GameInput, InputChange};
+
+pub struct DemoPlayer {
+    heuristic: Heuristic,
+    move_delay: u128, // Time in µs to wait after a piece enters the matrix before placing it, so the demo can be followed
+    timer: u128,
+    placed: bool, // The inputs for the current piece were already played
+}
+
+impl DemoPlayer {
+    pub fn new(move_delay: u128) -> Result<Self, String> {
+        Ok(Self {
+            heuristic: Heuristic::load()?,
+            move_delay,
+            timer: 0,
+            placed: false,
+        })
+    }
+
+    /* Return the inputs to play this update, through the same path as the keyboard */
+    pub fn update(&mut self, game: &Game, elapsed: u128) -> Vec<InputChange<GameInput>> {
+        if self.placed || game.is_over() || game.is_clearing_lines() {
+            return Vec::new();
+        }
+        self.timer += elapsed;
+        if self.timer < self.move_delay {
+            return Vec::new();
+        }
+        self.placed = true;
+        match game.best_placement(&self.heuristic) {
+            Some(placement) => taps(&placement.inputs),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn handle_events(&mut self, events: &[GameEvent]) {
+        if events.iter().any(|x| matches!(x, GameEvent::PieceSpawned)) {
+            self.placed = false;
+            self.timer = 0;
+        }
+    }
+}
@@ -0,0 +1,339 @@
+pub mod tbp;
+pub mod demo;
+
+use tbp::{BotMessage, FrontendMessage, Move};
+use crate::game::{Game, GameEvent, piece::Piece, search};
+use crate::input::{GameInput, InputChange};
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, TryRecvError},
+};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct BotConfig {
+    pub enabled: bool,
+    pub command: String, // Program that speaks the Tetris Bot Protocol over stdin and stdout
+    pub args: Vec<String>,
+}
+
+impl BotConfig {
+    pub fn load() -> Result<Self, String> {
+        crate::load_data_ron(Path::new("config/bot.ron"))
+    }
+}
+
+#[derive(PartialEq)]
+enum BotState {
+    Connecting, // Waiting for info
+    Preparing, // Waiting for ready after sending the rules
+    Idle, // Ready to start from the current state of the game
+    Thinking, // Waiting for a suggestion
+    Placing, // Inputs for the move were sent, waiting for the piece to lock
+    Placed, // Waiting for the next piece to enter the matrix
+    Stopped, // The bot failed or the game is over
+}
+
+/* Plays the game for an external bot process. The bot is told about each piece and its suggestions are turned into inputs */
+pub struct BotPlayer {
+    process: Child,
+    stdin: ChildStdin,
+    messages: Receiver<BotMessage>,
+    state: BotState,
+    queue: Vec<String>, // Pieces the bot knows about, starting with the one being placed
+    hold: Option<String>, // Piece the bot thinks is held
+}
+
+impl BotPlayer {
+    pub fn new(config: &BotConfig) -> Result<Self, String> {
+        let mut process = Command::new(&config.command)
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Could not start bot {}: {}", config.command, e))?;
+        let stdin = process.stdin.take().unwrap();
+        let stdout = process.stdout.take().unwrap();
+
+        // Read on another thread so that a slow bot never blocks the game
+        let (sender, messages) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(x) => x,
+                    Err(_) => break,
+                };
+                match serde_json::from_str::<BotMessage>(&line) {
+                    Ok(message) => {
+                        if sender.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    // Messages that are not understood are ignored, as the protocol requires
+                    Err(e) => eprintln!("Ignoring bot message {}: {}", line, e),
+                }
+            }
+        });
+
+        Ok(Self {
+            process,
+            stdin,
+            messages,
+            state: BotState::Connecting,
+            queue: Vec::new(),
+            hold: None,
+        })
+    }
+
+    /* Read the bot's messages and return the inputs to play this update */
+    pub fn update(&mut self, game: &Game) -> Vec<InputChange<GameInput>> {
+        let mut inputs = Vec::new();
+        loop {
+            let message = match self.messages.try_recv() {
+                Ok(x) => x,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if self.state != BotState::Stopped {
+                        eprintln!("Bot process exited");
+                        self.state = BotState::Stopped;
+                    }
+                    break;
+                }
+            };
+            match message {
+                BotMessage::Info { name, version, author } => {
+                    eprintln!("Connected to bot {} {} by {}", name, version, author);
+                    self.send(&FrontendMessage::Rules);
+                    self.state = BotState::Preparing;
+                }
+                BotMessage::Ready => {
+                    self.state = BotState::Idle;
+                }
+                BotMessage::Error { reason } => {
+                    eprintln!("Bot stopped: {}", reason);
+                    self.state = BotState::Stopped;
+                }
+                BotMessage::Suggestion { moves } => {
+                    if self.state == BotState::Thinking {
+                        inputs = self.play_suggestion(game, &moves);
+                    }
+                }
+            }
+        }
+
+        if game.is_over() || game.is_clearing_lines() {
+            return inputs;
+        }
+        match self.state {
+            BotState::Idle => self.start(game),
+            BotState::Placed => self.request_next(game),
+            _ => {}
+        }
+        inputs
+    }
+
+    /* The bot has to start over when something happens that it could not predict */
+    pub fn handle_events(&mut self, events: &[GameEvent]) {
+        for event in events {
+            match event {
+                GameEvent::GameEnded if self.state != BotState::Stopped => {
+                    self.send(&FrontendMessage::Stop);
+                    self.state = BotState::Stopped;
+                }
+                GameEvent::Locked if self.state == BotState::Placing => self.state = BotState::Placed,
+                // A piece locked before the suggestion arrived
+                GameEvent::Locked if self.state == BotState::Thinking => self.restart(),
+                GameEvent::RulesetChanged if self.state != BotState::Stopped => self.restart(),
+                _ => {}
+            }
+        }
+    }
+
+    fn restart(&mut self) {
+        if matches!(self.state, BotState::Thinking | BotState::Placing | BotState::Placed) {
+            self.send(&FrontendMessage::Stop);
+            self.state = BotState::Idle;
+        }
+    }
+
+    fn start(&mut self, game: &Game) {
+        let queue = upcoming_pieces(game);
+        if !tbp::is_supported_matrix(game.matrix()) || !queue.iter().all(|x| tbp::is_supported_piece(x)) {
+            eprintln!("Bot stopped: the gamemode uses a matrix or pieces that the bot protocol does not support");
+            self.state = BotState::Stopped;
+            return;
+        }
+
+        self.hold = game.held_piece().map(|x| x.name.clone());
+        self.queue = queue.clone();
+        self.send(&FrontendMessage::Start {
+            hold: self.hold.clone(),
+            queue,
+            combo: game.combo(),
+            back_to_back: game.back_to_back(),
+            board: tbp::board(game.matrix()),
+        });
+        self.send(&FrontendMessage::Suggest);
+        self.state = BotState::Thinking;
+    }
+
+    /* Tell the bot about pieces that entered the preview, then ask for the next move */
+    fn request_next(&mut self, game: &Game) {
+        let upcoming = upcoming_pieces(game);
+        let hold = game.held_piece().map(|x| x.name.clone());
+        if !upcoming.starts_with(&self.queue) || hold != self.hold {
+            self.restart();
+            return;
+        }
+        for piece in upcoming[self.queue.len()..].iter() {
+            if !tbp::is_supported_piece(piece) {
+                self.restart();
+                return;
+            }
+            self.send(&FrontendMessage::NewPiece { piece: piece.clone() });
+        }
+        self.queue = upcoming;
+        self.send(&FrontendMessage::Suggest);
+        self.state = BotState::Thinking;
+    }
+
+    /* Play the first suggestion that can be reached, restarting the bot if none can */
+    fn play_suggestion(&mut self, game: &Game, moves: &[Move]) -> Vec<InputChange<GameInput>> {
+        for piece_move in moves {
+            if let Some(inputs) = self.plan_move(game, piece_move) {
+                self.send(&FrontendMessage::Play { piece_move: piece_move.clone() });
+                self.advance_queue(&piece_move.location.piece);
+                self.state = BotState::Placing;
+                return taps(&inputs);
+            }
+        }
+        eprintln!("None of the bot's suggestions can be reached");
+        self.restart();
+        Vec::new()
+    }
+
+    fn plan_move(&self, game: &Game, piece_move: &Move) -> Option<Vec<GameInput>> {
+        let matrix = game.matrix();
+        let target = tbp::location_cells(&piece_move.location, matrix.len())?;
+        let current = game.current_piece();
+        let (piece, held) = if piece_move.location.piece == current.name {
+            (current.clone(), false)
+        } else if game.can_hold() {
+            let mut piece = match game.held_piece() {
+                Some(x) => x.clone(),
+                None => game.get_preview_pieces().last()?.clone(),
+            };
+            if piece.name != piece_move.location.piece {
+                return None;
+            }
+            piece.reset_position(matrix);
+            (piece, true)
+        } else {
+            return None;
+        };
+
+        let mut inputs = search::find_path(&piece, matrix, game.kick_data(), game.has_instant_gravity(), &target)?;
+        if held {
+            inputs.insert(0, GameInput::Hold);
+        }
+        Some(inputs)
+    }
+
+    /* Follow the protocol's rules for what the bot's queue looks like after a move */
+    fn advance_queue(&mut self, placed: &str) {
+        if self.queue.is_empty() {
+            return;
+        }
+        let current = self.queue.remove(0);
+        if current != placed {
+            match self.hold.replace(current) {
+                Some(_) => {}
+                // The first hold uses the next piece from the queue
+                None if !self.queue.is_empty() => {
+                    self.queue.remove(0);
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn send(&mut self, message: &FrontendMessage) {
+        let sent = serde_json::to_string(message)
+            .map_err(|e| e.to_string())
+            .and_then(|line| writeln!(self.stdin, "{}", line).map_err(|e| e.to_string()));
+        if let Err(e) = sent {
+            if self.state != BotState::Stopped {
+                eprintln!("Could not send to bot: {}", e);
+                self.state = BotState::Stopped;
+            }
+        }
+    }
+}
+
+impl Drop for BotPlayer {
+    fn drop(&mut self) {
+        self.send(&FrontendMessage::Quit);
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/* The piece being placed followed by the preview, in the order they will be played */
+fn upcoming_pieces(game: &Game) -> Vec<String> {
+    let mut pieces = vec![game.current_piece().name.clone()];
+    pieces.extend(game.get_preview_pieces().iter().rev().map(|x: &Piece| x.name.clone()));
+    pieces
+}
+
+/* Press and release each input in order at the start of the update */
+fn taps(inputs: &[GameInput]) -> Vec<InputChange<GameInput>> {
+    inputs.iter()
+        .flat_map(|input| [true, false].map(|pressed| InputChange { input: *input, pressed, timestamp: 0, time: 0 }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::TestGame;
+
+    const TICKS: u32 = 5000; // Game time the bot has to place a piece in
+    const WAITS: u32 = 3000; // Checks for a reply from the bot, 10 ms apart, before giving up
+
+    #[test]
+    fn dummy_bot_places_a_piece() {
+        // Cargo builds the bot if needed and knows where it is for any profile or target directory
+        let config = BotConfig {
+            enabled: true,
+            command: String::from(env!("CARGO")),
+            args: ["run", "--quiet", "--bin", "dummy_bot"].map(String::from).to_vec(),
+        };
+        let mut bot = BotPlayer::new(&config).unwrap();
+        let mut test = TestGame::new("marathon", 0);
+
+        for _ in 0..TICKS {
+            // The game waits while the bot works, so the result does not depend on how fast the bot is
+            let mut changes = bot.update(&test.game);
+            let mut waits = 0;
+            while matches!(bot.state, BotState::Connecting | BotState::Preparing | BotState::Thinking) {
+                assert!(waits < WAITS, "The bot did not reply in time");
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                changes.extend(bot.update(&test.game));
+                waits += 1;
+            }
+
+            test.update(&changes);
+            let events = test.game.take_events();
+            bot.handle_events(&events);
+            if events.iter().any(|x| matches!(x, GameEvent::Locked)) {
+                assert!(bot.state == BotState::Placed, "A piece locked without the bot placing it");
+                return;
+            }
+            assert!(bot.state != BotState::Stopped, "The bot stopped before placing a piece");
+        }
+        panic!("The bot did not place a piece in time");
+    }
+}
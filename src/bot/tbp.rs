@@ -0,0 +1,129 @@
+/* Messages of the Tetris Bot Protocol, sent as one JSON object per line */
+use crate::game::Matrix;
+use crate::game::piece::PieceColor;
+
+use serde::{Deserialize, Serialize};
+
+pub const BOARD_WIDTH: usize = 10;
+pub const BOARD_HEIGHT: usize = 40;
+const PIECES: [&str; 7] = ["I", "O", "T", "L", "J", "S", "Z"];
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules,
+    Start {
+        hold: Option<String>,
+        queue: Vec<String>, // The first piece is the one being placed
+        combo: u32,
+        back_to_back: bool,
+        board: Vec<Vec<Option<String>>>, // Rows from the bottom up
+    },
+    Stop,
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        piece_move: Move,
+    },
+    NewPiece {
+        piece: String,
+    },
+    Quit,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+    },
+    Ready,
+    Error {
+        reason: String,
+    },
+    Suggestion {
+        moves: Vec<Move>, // Best first
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Move {
+    pub location: PieceLocation,
+    pub spin: Spin,
+}
+
+/* x and y are the piece's center of rotation in SRS, y counts up from the bottom of the board */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PieceLocation {
+    #[serde(rename = "type")]
+    pub piece: String,
+    pub orientation: Orientation,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+pub fn is_supported_piece(name: &str) -> bool {
+    PIECES.contains(&name)
+}
+
+/* The protocol only describes a 10 wide board with up to 40 rows */
+pub fn is_supported_matrix(matrix: &Matrix) -> bool {
+    matrix[0].len() == BOARD_WIDTH && matrix.len() <= BOARD_HEIGHT
+}
+
+/* Any filled cell is sent as garbage, since locked blocks only keep their color */
+pub fn board(matrix: &Matrix) -> Vec<Vec<Option<String>>> {
+    let mut board = vec![vec![None; BOARD_WIDTH]; BOARD_HEIGHT];
+    for (y, row) in matrix.iter().rev().enumerate() {
+        for (x, color) in row.iter().enumerate() {
+            if *color != PieceColor::Empty {
+                board[y][x] = Some(String::from("G"));
+            }
+        }
+    }
+    board
+}
+
+/* Matrix [col, row] of each block of a piece at a location, sorted so they can be compared */
+pub fn location_cells(location: &PieceLocation, matrix_height: usize) -> Option<Vec<(i32, i32)>> {
+    let north: [(i32, i32); 4] = match location.piece.as_str() {
+        "I" => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+        "O" => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        "T" => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+        "L" => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+        "J" => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+        "S" => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+        "Z" => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+        _ => return None,
+    };
+    let mut cells: Vec<(i32, i32)> = north.iter()
+        .map(|(x, y)| match location.orientation {
+            Orientation::North => (*x, *y),
+            Orientation::East => (*y, -*x),
+            Orientation::South => (-*x, -*y),
+            Orientation::West => (-*y, *x),
+        })
+        .map(|(x, y)| (location.x + x, matrix_height as i32 - 1 - (location.y + y)))
+        .collect();
+    cells.sort_unstable();
+    Some(cells)
+}
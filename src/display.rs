@@ -0,0 +1,121 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use sdl2::{
+    keyboard::Scancode,
+    render::Canvas,
+    sys::{SDL_RenderSetIntegerScale, SDL_bool},
+    video::{FullscreenType, Window},
+};
+
+/* Size that everything is drawn at before being scaled to the window */
+pub const LOGICAL_WIDTH: u32 = 640;
+pub const LOGICAL_HEIGHT: u32 = 360;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    Borderless, // Fullscreen at the desktop resolution
+    Fullscreen, // Exclusive fullscreen, which changes the display mode to the window size
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct DisplayConfig {
+    pub mode: WindowMode,
+    pub width: u32,
+    pub height: u32,
+    pub integer_scaling: bool, // Only scale by whole numbers, leaving a border instead of stretching pixels unevenly
+    pub vsync: bool,
+    pub frame_cap: u32, // Frames per second when vsync is off, 0 for unlimited
+    pub fullscreen_key: String, // Scancode name of the key that toggles fullscreen
+    pub reload_key: String, // Scancode name of the key that reloads these settings from config.toml, and the sound settings
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            mode: WindowMode::Windowed,
+            width: 1280,
+            height: 720,
+            integer_scaling: false,
+            vsync: true,
+            frame_cap: 0,
+            fullscreen_key: String::from("F11"),
+            reload_key: String::from("F5"),
+        }
+    }
+}
+
+impl DisplayConfig {
+    pub fn fullscreen_scancode(&self) -> Result<Scancode, String> {
+        read_scancode(&self.fullscreen_key)
+    }
+
+    pub fn reload_scancode(&self) -> Result<Scancode, String> {
+        read_scancode(&self.reload_key)
+    }
+
+    /* The fullscreen type used by the mode, and by the toggle key when starting windowed */
+    fn fullscreen_type(&self) -> FullscreenType {
+        match self.mode {
+            WindowMode::Windowed | WindowMode::Borderless => FullscreenType::Desktop,
+            WindowMode::Fullscreen => FullscreenType::True,
+        }
+    }
+}
+
+fn read_scancode(name: &str) -> Result<Scancode, String> {
+    Scancode::from_name(name).ok_or(format!("Display settings have an unknown key name: {}", name))
+}
+
+/* Vsync can only be chosen when the renderer is created, so changing it means building a new canvas */
+pub fn create_canvas(window: Window, config: &DisplayConfig) -> Result<Canvas<Window>, String> {
+    let mut builder = window.into_canvas();
+    if config.vsync {
+        builder = builder.present_vsync();
+    }
+    let mut canvas = builder.build()
+        .map_err(|e| e.to_string())?;
+    apply(&mut canvas, config)?;
+    Ok(canvas)
+}
+
+/* Apply the settings that can be changed on an existing canvas */
+pub fn apply(canvas: &mut Canvas<Window>, config: &DisplayConfig) -> Result<(), String> {
+    let window = canvas.window_mut();
+    // The size has to be set while windowed, otherwise it would change the fullscreen display mode instead
+    window.set_fullscreen(FullscreenType::Off)?;
+    window.set_size(config.width, config.height)
+        .map_err(|e| e.to_string())?;
+    if config.mode != WindowMode::Windowed {
+        window.set_fullscreen(config.fullscreen_type())?;
+    }
+
+    canvas.set_logical_size(LOGICAL_WIDTH, LOGICAL_HEIGHT)
+        .map_err(|e| e.to_string())?;
+    let enable = if config.integer_scaling { SDL_bool::SDL_TRUE } else { SDL_bool::SDL_FALSE };
+    if unsafe { SDL_RenderSetIntegerScale(canvas.raw(), enable) } != 0 {
+        return Err(sdl2::get_error());
+    }
+    Ok(())
+}
+
+pub fn toggle_fullscreen(canvas: &mut Canvas<Window>, config: &DisplayConfig) -> Result<(), String> {
+    let window = canvas.window_mut();
+    match window.fullscreen_state() {
+        FullscreenType::Off => window.set_fullscreen(config.fullscreen_type()),
+        _ => window.set_fullscreen(FullscreenType::Off),
+    }
+}
+
+/* Sleep for the rest of the frame when vsync is off and the frame rate is capped */
+pub fn limit_frame_rate(frame_start: Instant, config: &DisplayConfig) {
+    if config.vsync || config.frame_cap == 0 {
+        return;
+    }
+    let frame_length = Duration::from_micros(1_000_000 / config.frame_cap as u64);
+    if let Some(remaining) = frame_length.checked_sub(frame_start.elapsed()) {
+        std::thread::sleep(remaining);
+    }
+}
@@ -1,93 +1,139 @@
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 
 use enum_map::{EnumMap, enum_map};
 use sdl2::{image::LoadTexture, pixels::Color, rect::{Rect}, render::{Texture, BlendMode, TextureCreator}, ttf::{Font, Sdl2TtfContext}, video::WindowContext};
 
 use crate::game::configuration::{EndCondition, GameStat};
-use crate::assets::create_text_texture;
+use crate::input::GameInput;
+use crate::assets::{create_text_texture, CachedText, GlyphAtlas};
+use crate::theme::{Theme, ConnectedRegions};
 
 use super::Stats;
 
+/* Characters that can appear in stats and the next level label */
+const STAT_CHARACTERS: &str = "0123456789:.-SLP ";
+
 pub struct GameAssets<'a, 'b> {
+    texture_creator: &'a TextureCreator<WindowContext>,
     pub block_sheet: Texture<'a>,
     pub block_sprites: Vec<Rect>,
-    pub stat_font: Font<'a, 'b>,
-    pub next_level_font: Font<'a, 'b>,
+    pub connected_block_sprites: Option<ConnectedRegions>,
+    pub stat_glyphs: GlyphAtlas<'a>,
+    pub next_level_glyphs: GlyphAtlas<'a>,
+    next_level_font: Font<'a, 'b>,
+    popup_font: Font<'a, 'b>,
+    popup_textures: HashMap<String, Texture<'a>>,
+    input_count_text: CachedText<'a>,
     pub stat_labels: EnumMap<GameStat, Texture<'a>>,
     pub frame: Texture<'a>,
+    pub background: Option<Texture<'a>>,
     pub gamemode_name_texture: Texture<'a>,
+    pub input_labels: EnumMap<GameInput, Texture<'a>>,
+    pub background_color: Color,
+    pub ghost_outline_color: Color,
+    pub hint_color: Color,
+    next_level_color: Color,
 }
 
 impl<'a, 'b> GameAssets<'a, 'b> {
-    pub fn new(texture_creator: &'a TextureCreator<WindowContext>, ttf_context: &'a Sdl2TtfContext, gamemode_name: &str) -> Result<Self, String> {
-        let (block_sheet, block_sprites) = load_block_textures(texture_creator, Path::new("assets/blocks.png"))?;
-        let stat_font = ttf_context.load_font(Path::new("assets/Hack-Bold.ttf"), 28)?;
-        let next_level_font = ttf_context.load_font(Path::new("assets/Hack-Bold.ttf"), 14)?;
-        let label_font = ttf_context.load_font(Path::new("assets/Hack-Bold.ttf"), 18)?;
-        let stat_labels = load_stat_labels(texture_creator, label_font)?;
-        let frame = load_frame(texture_creator, Path::new("assets/frame.png"))?;
-        let gamemode_name_texture = load_gamemode_name_texture(texture_creator, ttf_context, Path::new("assets/Hack-Bold.ttf"), gamemode_name)?;
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>, ttf_context: &'a Sdl2TtfContext, theme: &Theme, gamemode_name: &str, background: &Option<String>) -> Result<Self, String> {
+        let (block_sheet, block_sprites, connected_block_sprites) = load_block_textures(texture_creator, theme)?;
+        let stat_font = ttf_context.load_font(Path::new(&theme.fonts.stat), 28)?;
+        let stat_glyphs = GlyphAtlas::new(STAT_CHARACTERS, Color::from(theme.colors.stat), &stat_font, texture_creator)?;
+        let next_level_font = ttf_context.load_font(Path::new(&theme.fonts.stat), 14)?;
+        let next_level_glyphs = GlyphAtlas::new(STAT_CHARACTERS, Color::from(theme.colors.next_level), &next_level_font, texture_creator)?;
+        let label_font = ttf_context.load_font(Path::new(&theme.fonts.label), 18)?;
+        let popup_font = ttf_context.load_font(Path::new(&theme.fonts.label), 16)?;
+        let stat_labels = load_stat_labels(texture_creator, label_font, Color::from(theme.colors.stat_label))?;
+        let frame = theme.load_texture(texture_creator, &theme.frame)?;
+        let background = match background {
+            Some(path) => Some(texture_creator.load_texture(path)
+                .map_err(|e| format!("Gamemode {} could not load background {}: {}", gamemode_name, path, e))?),
+            None => None,
+        };
+        let gamemode_name_texture = load_gamemode_name_texture(texture_creator, ttf_context, Path::new(&theme.fonts.stat), gamemode_name, Color::from(theme.colors.gamemode_name))?;
+        let input_label_font = ttf_context.load_font(Path::new(&theme.fonts.label), 9)?;
+        let input_labels = load_input_labels(texture_creator, input_label_font)?;
 
         Ok(Self {
+            texture_creator,
             block_sheet,
             block_sprites,
-            stat_font,
+            connected_block_sprites,
+            stat_glyphs,
+            next_level_glyphs,
             next_level_font,
+            popup_font,
+            popup_textures: HashMap::new(),
+            input_count_text: CachedText::new(),
             stat_labels,
             frame,
+            background,
             gamemode_name_texture,
+            input_labels,
+            background_color: Color::from(theme.colors.background),
+            ghost_outline_color: Color::from(theme.colors.ghost_outline),
+            hint_color: Color::from(theme.colors.hint),
+            next_level_color: Color::from(theme.colors.next_level),
         })
     }
 
-    pub fn create_stat_textures<'c>(&self, stats: &super::Stats, level: usize, texture_creator: &'c TextureCreator<WindowContext>) -> Result<EnumMap<GameStat, Texture<'c>>, String> {
-        let color = Color::RGB(255, 255, 255);
-
-        let textures = enum_map! {
-            GameStat::Score => create_text_texture(&stats.score.to_string(), color, &self.stat_font, texture_creator)?,
-            GameStat::Time => create_text_texture(&format_time(stats.time), color, &self.stat_font, texture_creator)?,
-            GameStat::Lines => create_text_texture(&stats.lines_cleared.to_string(), color, &self.stat_font, texture_creator)?,
-            GameStat::Pieces => create_text_texture(&stats.pieces_placed.to_string(), color, &self.stat_font, texture_creator)?,
-            GameStat::Level => create_text_texture(&level.to_string(), color, &self.stat_font, texture_creator)?,
-            GameStat::PiecesPerSecond => create_text_texture(&format!("{:.3}", stats.pieces_per_second()), color, &self.stat_font, texture_creator)?,
-        };
+    /* Popup text is cached since the same few messages are shown repeatedly */
+    pub fn popup_texture(&mut self, text: &str) -> Result<&mut Texture<'a>, String> {
+        if !self.popup_textures.contains_key(text) {
+            let texture = create_text_texture(text, Color::RGB(255, 255, 255), &self.popup_font, self.texture_creator)?;
+            self.popup_textures.insert(text.to_string(), texture);
+        }
+        Ok(self.popup_textures.get_mut(text).unwrap())
+    }
 
-        Ok(textures)
+    pub fn input_count_texture(&mut self, text: &str) -> Result<&Texture<'a>, String> {
+        self.input_count_text.get(text, self.next_level_color, &self.next_level_font, self.texture_creator)
     }
+}
 
-    pub fn create_next_level_label<'c>(&self, level_up_cond: &EndCondition, level_stats: &Stats, texture_creator: &'c TextureCreator<WindowContext>) -> Result<Texture<'c>, String> {
-        let text = match &level_up_cond {
-            EndCondition::Time(min, sec) => {
-                let microseconds = (min * 60 + sec) as u128 * 1_000_000;
-                let remaining = microseconds.saturating_sub(level_stats.time);
-                let mut time = format_time(remaining);
-                time.truncate(5);
-                time
-            }
-            EndCondition::Score(next) => format!("{}S", next - level_stats.score),
-            EndCondition::Lines(next) => format!("{}L", next - level_stats.lines_cleared),
-            EndCondition::Pieces(next) => format!("{}P", next - level_stats.pieces_placed),
-            EndCondition::Endless => String::from(" "),
-        };
+pub fn stat_text(stats: &Stats, level: usize) -> EnumMap<GameStat, String> {
+    enum_map! {
+        GameStat::Score => stats.score.to_string(),
+        GameStat::Time => format_time(stats.time),
+        GameStat::Lines => stats.lines_cleared.to_string(),
+        GameStat::Pieces => stats.pieces_placed.to_string(),
+        GameStat::Level => level.to_string(),
+        GameStat::PiecesPerSecond => format!("{:.3}", stats.pieces_per_second()),
+        GameStat::FinesseFaults => stats.finesse_faults.to_string(),
+    }
+}
 
-        let color = Color::RGB(192, 192, 192);
-        create_text_texture(&text, color, &self.next_level_font, texture_creator)
+pub fn next_level_text(level_up_cond: &EndCondition, level_stats: &Stats) -> String {
+    match &level_up_cond {
+        EndCondition::Time(min, sec) => {
+            let microseconds = (min * 60 + sec) as u128 * 1_000_000;
+            let remaining = microseconds.saturating_sub(level_stats.time);
+            let mut time = format_time(remaining);
+            time.truncate(5);
+            time
+        }
+        EndCondition::Score(next) => format!("{}S", next - level_stats.score),
+        EndCondition::Lines(next) => format!("{}L", next - level_stats.lines_cleared),
+        EndCondition::Pieces(next) => format!("{}P", next - level_stats.pieces_placed),
+        EndCondition::Endless => String::from(" "),
     }
 }
 
-fn load_gamemode_name_texture<'a>(texture_creator: &'a TextureCreator<WindowContext>, ttf_context: &'a Sdl2TtfContext, path: &Path, gamemode_name: &str) -> Result<Texture<'a>, String> {
+fn load_gamemode_name_texture<'a>(texture_creator: &'a TextureCreator<WindowContext>, ttf_context: &'a Sdl2TtfContext, path: &Path, gamemode_name: &str, color: Color) -> Result<Texture<'a>, String> {
     let font = ttf_context.load_font(path, 72)?;
-    create_text_texture(gamemode_name, Color::RGBA(0, 0, 0, 64), &font, texture_creator)
+    create_text_texture(gamemode_name, color, &font, texture_creator)
 }
 
-fn load_block_textures<'a>(texture_creator: &'a TextureCreator<WindowContext>, path: &Path) -> Result<(Texture<'a>, Vec<Rect>), String> {
-    let mut block_sheet = texture_creator.load_texture(path)?;
-    let block_sprites = block_texture_regions(&block_sheet)?;
+fn load_block_textures<'a>(texture_creator: &'a TextureCreator<WindowContext>, theme: &Theme) -> Result<(Texture<'a>, Vec<Rect>, Option<ConnectedRegions>), String> {
+    let mut block_sheet = theme.load_texture(texture_creator, &theme.block_sheet)?;
+    let block_sprites = theme.block_regions(&block_sheet)?;
+    let connected_block_sprites = theme.connected_block_regions(&block_sheet)?;
     block_sheet.set_blend_mode(BlendMode::Blend);
-    Ok((block_sheet, block_sprites))
+    Ok((block_sheet, block_sprites, connected_block_sprites))
 }
 
-fn load_stat_labels<'a>(texture_creator: &'a TextureCreator<WindowContext>, label_font: Font) -> Result<EnumMap<GameStat, Texture<'a>>, String> {
-    let label_color = Color::RGB(144, 144, 144);
+fn load_stat_labels<'a>(texture_creator: &'a TextureCreator<WindowContext>, label_font: Font, label_color: Color) -> Result<EnumMap<GameStat, Texture<'a>>, String> {
     let stat_labels = enum_map! {
         GameStat::Score => create_text_texture("Score", label_color, &label_font, texture_creator)?,
         GameStat::Time => create_text_texture("Time", label_color, &label_font, texture_creator)?,
@@ -95,13 +141,33 @@ fn load_stat_labels<'a>(texture_creator: &'a TextureCreator<WindowContext>, labe
         GameStat::Pieces => create_text_texture("Pieces", label_color, &label_font, texture_creator)?,
         GameStat::Level => create_text_texture("Level", label_color, &label_font, texture_creator)?,
         GameStat::PiecesPerSecond => create_text_texture("Pieces/Second", label_color, &label_font, texture_creator)?,
+        GameStat::FinesseFaults => create_text_texture("Faults", label_color, &label_font, texture_creator)?,
     };
 
     Ok(stat_labels)
 }
 
-fn load_frame<'a>(texture_creator: &'a TextureCreator<WindowContext>, path: &Path) -> Result<Texture<'a>, String> {
-    texture_creator.load_texture(path)
+fn load_input_labels<'a>(texture_creator: &'a TextureCreator<WindowContext>, label_font: Font) -> Result<EnumMap<GameInput, Texture<'a>>, String> {
+    let label_color = Color::RGB(255, 255, 255);
+    let input_labels = enum_map! {
+        GameInput::HardDrop => create_text_texture("HD", label_color, &label_font, texture_creator)?,
+        GameInput::InstantDrop => create_text_texture("ID", label_color, &label_font, texture_creator)?,
+        GameInput::SoftDrop => create_text_texture("SD", label_color, &label_font, texture_creator)?,
+        GameInput::Left => create_text_texture("L", label_color, &label_font, texture_creator)?,
+        GameInput::ShiftLeft => create_text_texture("<<", label_color, &label_font, texture_creator)?,
+        GameInput::Right => create_text_texture("R", label_color, &label_font, texture_creator)?,
+        GameInput::ShiftRight => create_text_texture(">>", label_color, &label_font, texture_creator)?,
+        GameInput::RotateCW => create_text_texture("CW", label_color, &label_font, texture_creator)?,
+        GameInput::Rotate180 => create_text_texture("180", label_color, &label_font, texture_creator)?,
+        GameInput::RotateCCW => create_text_texture("CCW", label_color, &label_font, texture_creator)?,
+        GameInput::Hold => create_text_texture("HLD", label_color, &label_font, texture_creator)?,
+        GameInput::Reset => create_text_texture("RST", label_color, &label_font, texture_creator)?,
+        GameInput::Pause => create_text_texture("PSE", label_color, &label_font, texture_creator)?,
+        GameInput::Undo => create_text_texture("UND", label_color, &label_font, texture_creator)?,
+        GameInput::Redo => create_text_texture("RED", label_color, &label_font, texture_creator)?,
+    };
+
+    Ok(input_labels)
 }
 
 fn format_time(microseconds: u128) -> String {
@@ -112,18 +178,3 @@ fn format_time(microseconds: u128) -> String {
 
     format!("{:>0width$}:{:>0width$}.{:>0width$}", minutes, seconds, hundredths, width=2)
 }
-
-fn block_texture_regions(texture: &Texture) -> Result<Vec<Rect>, String> {
-    let mut regions = Vec::new();
-    let query = texture.query();
-
-    for i in 0..super::piece::PieceColor::ColorCount as i32 {
-        let offset = i*query.height as i32;
-        if offset >= query.width as i32 {
-            return Err("Block texture file is not properly formed".to_string());
-        }
-        regions.push(Rect::new(offset, 0, query.height, query.height));
-    }
-
-    Ok(regions)
-}
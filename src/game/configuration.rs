@@ -1,10 +1,10 @@
 use super::randomizer::{self, RandomizerStyle};
 
-use std::{path::Path, collections::HashSet};
+use std::path::Path;
 
-use serde::{Deserialize};
+use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone, Copy)]
 pub enum EndCondition {
     Time(u32, u32),
     Score(u32),
@@ -36,14 +36,14 @@ impl EndCondition {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub enum Goal {
     Time,
     Score,
     Lines,
 }
 
-#[derive(Deserialize, enum_map::Enum, Clone, Copy)]
+#[derive(Serialize, Deserialize, enum_map::Enum, Clone, Copy)]
 pub enum GameStat {
     Score,
     Time,
@@ -51,11 +51,12 @@ pub enum GameStat {
     Pieces,
     Level,
     PiecesPerSecond,
+    FinesseFaults,
     // Streak,
     // Combo,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub enum RulesetModifier {
     LoadRuleset(String),
     SetCondition(EndCondition),
@@ -66,23 +67,26 @@ pub enum RulesetModifier {
     AddPiece(String),
     RemovePiece(String),
     SetLockDelay(u128),
+    SetLineClearDelay(u128),
     SetPreviewCount(usize),
     CanHold(bool),
     ShowGhostPiece(bool),
     ChangeRandomizer(RandomizerStyle),
     ClearMatrix,
+    PlayMusic(String),
     End,
 }
 
-#[derive(Hash, PartialEq, Eq)]
+#[derive(PartialEq, Eq)]
 pub enum Command {
     RegeneratePieces,
     ResizeMatrix,
     ClearMatrix,
+    PlayMusic(String),
     End,
 }
 
-#[derive(Deserialize, Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone, Copy)]
 pub enum ScoreMultiplier {
     Level,
     Special(u32),
@@ -97,23 +101,33 @@ impl ScoreMultiplier {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct GameMode {
     pub end_condition: EndCondition,
     pub goal: Goal,
     pub displayed_stats: Vec<GameStat>,
     pub level_list: Vec<Vec<RulesetModifier>>,
     pub initial_ruleset: String,
+    pub layout: String,
+    pub background: Option<String>,
+    pub music: Option<String>, // Name of the track from the sound config played from level 1
+    #[serde(default)]
+    pub show_hint: bool, // Outline the best placement of each piece, scored by config/heuristic.ron
+    #[serde(default)]
+    pub finesse_retry: bool, // Put the piece back instead of locking it if it has a finesse fault or misses the hint
 }
 
 impl GameMode {
-    pub fn level_up(&self, ruleset: &mut Ruleset, level: usize) -> Result<HashSet<Command>, String> {
-        let mut commands = HashSet::new();
+    /* Commands are in the order the modifiers asked for them, so that every game applies them the same way */
+    pub fn level_up(&self, ruleset: &mut Ruleset, level: usize) -> Result<Vec<Command>, String> {
+        let mut commands = Vec::new();
         // Minus 2 because level starts at 1, arrays start at 0, and the level 1 ruleset is not in the list
         if level-2 < self.level_list.len() {
             for modifier in self.level_list[level-2].iter() {
-                if let Some(new_commands) = ruleset.apply_modifier(modifier)? {
-                    commands.extend(new_commands);
+                for command in ruleset.apply_modifier(modifier)?.unwrap_or_default() {
+                    if !commands.contains(&command) {
+                        commands.push(command);
+                    }
                 }
             }
         }
@@ -133,7 +147,7 @@ impl GameMode {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Ruleset {
     pub level_up_condition: EndCondition,
     pub score_multiplier: ScoreMultiplier,
@@ -143,6 +157,7 @@ pub struct Ruleset {
  
     pub gravity: u128,
     pub lock_delay: u128,
+    pub line_clear_delay: u128,
     pub preview_count: usize,
     pub hold_enabled: bool,
     pub ghost_piece_enabled: bool,
@@ -151,6 +166,14 @@ pub struct Ruleset {
     pub cannot_start_with: Option<Vec<String>>,
     pub starting_randomizer: Option<randomizer::RandomizerStyle>,
     pub randomizer: randomizer::RandomizerStyle,
+    #[serde(default)]
+    pub practice: bool, // Placements can be undone and redone
+    #[serde(default = "default_undo_limit")]
+    pub undo_limit: usize, // Placements that can be undone, older ones are forgotten
+}
+
+fn default_undo_limit() -> usize {
+    100
 }
 
 impl Ruleset {
@@ -191,6 +214,9 @@ impl Ruleset {
             RulesetModifier::SetLockDelay(x) => {
                 self.lock_delay = *x;
             }
+            RulesetModifier::SetLineClearDelay(x) => {
+                self.line_clear_delay = *x;
+            }
             RulesetModifier::SetPreviewCount(x) => {
                 self.preview_count = *x;
             }
@@ -207,6 +233,9 @@ impl Ruleset {
             RulesetModifier::ClearMatrix => {
                 return Ok(Some(vec![Command::ClearMatrix]));
             }
+            RulesetModifier::PlayMusic(track) => {
+                return Ok(Some(vec![Command::PlayMusic(track.to_string())]));
+            }
             RulesetModifier::End => {
                 return Ok(Some(vec![Command::End]));
             }
@@ -0,0 +1,161 @@
+use super::LineClear;
+use super::piece::PieceColor;
+
+use rand::Rng;
+use serde::Deserialize;
+
+/* Durations are in milliseconds */
+#[derive(Deserialize)]
+pub struct EffectsConfig {
+    pub enabled: bool,
+    pub flash_duration: u32,
+    pub particles_per_block: u32,
+    pub particle_duration: u32,
+    pub popup_duration: u32,
+}
+
+/* A cleared row fading out */
+pub struct RowFlash {
+    pub row: usize,
+    pub age: u128,
+}
+
+/* Positions and velocities are in grid squares, relative to the top left of the matrix */
+pub struct Particle {
+    pub x: f64,
+    pub y: f64,
+    velocity_x: f64,
+    velocity_y: f64,
+    pub color: PieceColor,
+    pub age: u128,
+}
+
+/* Text shown over the matrix after a notable clear */
+pub struct Popup {
+    pub text: String,
+    pub row: usize,
+    pub age: u128,
+}
+
+/* Visual effects for line clears, these only read from the game so gameplay is not affected */
+pub struct Effects {
+    config: EffectsConfig,
+    pub flashes: Vec<RowFlash>,
+    pub particles: Vec<Particle>,
+    pub popups: Vec<Popup>,
+}
+
+impl Effects {
+    pub fn new(config: EffectsConfig) -> Self {
+        Self {
+            config,
+            flashes: Vec::new(),
+            particles: Vec::new(),
+            popups: Vec::new(),
+        }
+    }
+
+    pub fn add_line_clear(&mut self, clear: &LineClear) {
+        if !self.config.enabled {
+            return;
+        }
+
+        for row in clear.rows.iter() {
+            self.flashes.push(RowFlash { row: *row, age: 0 });
+        }
+
+        let mut rng = rand::thread_rng();
+        for (row, blocks) in clear.rows.iter().zip(clear.blocks.iter()) {
+            for (col, color) in blocks.iter().enumerate() {
+                for _ in 0..self.config.particles_per_block {
+                    self.particles.push(Particle {
+                        x: col as f64 + 0.5,
+                        y: *row as f64 + 0.5,
+                        velocity_x: rng.gen_range(-4.0..4.0),
+                        velocity_y: rng.gen_range(-8.0..-2.0),
+                        color: *color,
+                        age: 0,
+                    });
+                }
+            }
+        }
+
+        let top_row = clear.rows.iter().min().copied().unwrap_or(0);
+        for text in popup_text(clear) {
+            self.popups.push(Popup { text, row: top_row, age: 0 });
+        }
+    }
+
+    pub fn update(&mut self, elapsed: u128) {
+        let seconds = elapsed as f64 / 1_000_000.0;
+        for particle in self.particles.iter_mut() {
+            particle.age += elapsed;
+            particle.x += particle.velocity_x * seconds;
+            particle.y += particle.velocity_y * seconds;
+            particle.velocity_y += 20.0 * seconds;
+        }
+        for flash in self.flashes.iter_mut() {
+            flash.age += elapsed;
+        }
+        for popup in self.popups.iter_mut() {
+            popup.age += elapsed;
+        }
+
+        let flash_duration = self.flash_duration();
+        let particle_duration = self.particle_duration();
+        let popup_duration = self.popup_duration();
+        self.flashes.retain(|x| x.age < flash_duration);
+        self.particles.retain(|x| x.age < particle_duration);
+        self.popups.retain(|x| x.age < popup_duration);
+    }
+
+    /* Durations converted to microseconds */
+    pub fn flash_duration(&self) -> u128 {
+        self.config.flash_duration as u128 * 1000
+    }
+
+    pub fn particle_duration(&self) -> u128 {
+        self.config.particle_duration as u128 * 1000
+    }
+
+    pub fn popup_duration(&self) -> u128 {
+        self.config.popup_duration as u128 * 1000
+    }
+}
+
+fn popup_text(clear: &LineClear) -> Vec<String> {
+    let mut text = Vec::new();
+    if clear.perfect_clear {
+        text.push(String::from("PERFECT CLEAR"));
+    }
+
+    let lines = match clear.rows.len() {
+        1 => String::from("SINGLE"),
+        2 => String::from("DOUBLE"),
+        3 => String::from("TRIPLE"),
+        4 => String::from("QUAD"),
+        x => format!("{} LINES", x),
+    };
+    match (clear.spin, clear.rows.len() >= 4) {
+        (true, _) => text.push(format!("SPIN {}", lines)),
+        (false, true) => text.push(lines),
+        _ => {}
+    }
+
+    if clear.back_to_back {
+        text.push(String::from("BACK-TO-BACK"));
+    }
+    if clear.combo > 0 {
+        text.push(format!("{} COMBO", clear.combo));
+    }
+    text
+}
+
+/* How far along an effect is, from 0.0 to 1.0 */
+pub fn progress(age: u128, duration: u128) -> f64 {
+    if duration == 0 {
+        1.0
+    } else {
+        f64::min(age as f64 / duration as f64, 1.0)
+    }
+}
@@ -0,0 +1,86 @@
+/* Garbage sent between games in versus. Attacks come from line clears and wait in the receiver's meter before entering the matrix */
+use super::LineClear;
+
+use serde::{Deserialize, Serialize};
+
+/* Lines of garbage sent for each kind of clear. Lists are indexed by lines cleared or by combo,
+   and the last entry is used for anything past the end */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AttackTable {
+    pub lines: Vec<u32>,
+    pub spins: Vec<u32>,
+    pub back_to_back: u32, // Added to clears that are back-to-back
+    pub combo: Vec<u32>, // Added by the number of clearing placements before this one
+    pub perfect_clear: u32, // Added when the clear empties the matrix
+    pub delay: u32, // Milliseconds that garbage waits in the meter before it can enter the matrix
+}
+
+impl AttackTable {
+    pub fn attack(&self, clear: &LineClear) -> u32 {
+        let table = if clear.spin { &self.spins } else { &self.lines };
+        let mut lines = lookup(table, clear.rows.len());
+        lines += lookup(&self.combo, clear.combo as usize);
+        if clear.back_to_back {
+            lines += self.back_to_back;
+        }
+        if clear.perfect_clear {
+            lines += self.perfect_clear;
+        }
+        lines
+    }
+}
+
+fn lookup(table: &[u32], index: usize) -> u32 {
+    table.get(index).or_else(|| table.last()).copied().unwrap_or(0)
+}
+
+/* One attack waiting in the meter, all of its lines share a hole */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct IncomingGarbage {
+    pub lines: u32,
+    pub delay: u128, // Time in µs left before it can enter the matrix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> AttackTable {
+        AttackTable {
+            lines: vec![0, 0, 1, 2, 4],
+            spins: vec![0, 2, 4, 6],
+            back_to_back: 1,
+            combo: vec![0, 0, 1, 2],
+            perfect_clear: 10,
+            delay: 500,
+        }
+    }
+
+    fn clear(lines: usize, spin: bool, back_to_back: bool, perfect_clear: bool, combo: u32) -> LineClear {
+        LineClear {
+            rows: (0..lines).collect(),
+            blocks: Vec::new(),
+            spin,
+            back_to_back,
+            perfect_clear,
+            combo,
+        }
+    }
+
+    #[test]
+    fn attack_adds_every_bonus() {
+        let table = table();
+        assert_eq!(table.attack(&clear(1, false, false, false, 0)), 0);
+        assert_eq!(table.attack(&clear(4, false, false, false, 0)), 4);
+        assert_eq!(table.attack(&clear(2, true, true, false, 0)), 5);
+        assert_eq!(table.attack(&clear(3, false, false, false, 2)), 3);
+        assert_eq!(table.attack(&clear(1, false, false, true, 0)), 10);
+    }
+
+    #[test]
+    fn attack_uses_the_last_entry_past_the_end() {
+        let table = table();
+        assert_eq!(table.attack(&clear(5, false, false, false, 0)), 4);
+        assert_eq!(table.attack(&clear(4, true, false, false, 9)), 8);
+    }
+}
@@ -0,0 +1,35 @@
+/* Hashes of games that online versus players compare to find desyncs.
+   FNV-1a over fixed width little endian values, so every build and platform hashes the same game the same way.
+   The standard library's hashers may change between Rust releases */
+pub struct StateHasher {
+    hash: u64,
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self { hash: 0xCBF2_9CE4_8422_2325 }
+    }
+}
+
+impl StateHasher {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /* The length goes first so that neighbouring strings can not run into each other */
+    pub fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write(value.as_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
@@ -0,0 +1,191 @@
+/* Undo and redo of placements, for rulesets marked as practice.
+   The state of the game is kept from when each piece entered the matrix, undoing goes back to the one before the last placement */
+use super::{Game, GameEvent, Stats, piece::{Piece, PieceColor}, configuration::Ruleset, randomizer::Randomizer, garbage::IncomingGarbage, rng::GameRng};
+
+use std::collections::VecDeque;
+
+#[derive(Default)]
+pub struct History {
+    spawn: Option<GameState>, // The game when the current piece entered the matrix
+    undo: VecDeque<GameState>, // Oldest first, no longer than the ruleset's undo limit
+    redo: Vec<GameState>,
+}
+
+/* Everything a placement can change */
+#[derive(Clone)]
+struct GameState {
+    matrix: Vec<Vec<PieceColor>>,
+    connections: Vec<Vec<u8>>,
+    piece: Piece,
+    held: Option<Piece>,
+    piece_queue: Vec<Piece>,
+    ruleset: Ruleset,
+    stats: Stats,
+    level_stats: Stats,
+    randomizer: Randomizer,
+    level: usize,
+    combo: u32,
+    can_hold: bool,
+    prev_clear_was_fancy: bool,
+    incoming_garbage: Vec<IncomingGarbage>,
+    garbage_rng: GameRng,
+    music: Option<String>,
+    game_over: bool,
+}
+
+impl Game {
+    /* Called when a piece enters the matrix, but not when it comes out of hold since holding is part of the same placement */
+    pub(super) fn record_spawn(&mut self) {
+        if self.ruleset.practice && self.can_hold {
+            self.history.spawn = Some(self.capture());
+        }
+    }
+
+    /* Called when a piece locks, a new placement means there is nothing left to redo */
+    pub(super) fn record_placement(&mut self) {
+        if let Some(state) = self.history.spawn.take() {
+            self.history.undo.push_back(state);
+            while self.history.undo.len() > self.ruleset.undo_limit {
+                self.history.undo.pop_front();
+            }
+            self.history.redo.clear();
+        }
+    }
+
+    /* Only works while a piece is in the matrix, not during the line clear delay */
+    pub(super) fn undo(&mut self) {
+        if self.history.spawn.is_none() {
+            return;
+        }
+        if let Some(state) = self.history.undo.pop_back() {
+            let current = self.history.spawn.take().unwrap();
+            self.history.redo.push(current);
+            self.restore(state);
+            self.used_undo = true;
+        }
+    }
+
+    pub(super) fn redo(&mut self) {
+        if self.history.spawn.is_none() {
+            return;
+        }
+        if let Some(state) = self.history.redo.pop() {
+            let current = self.history.spawn.take().unwrap();
+            self.history.undo.push_back(current);
+            self.restore(state);
+        }
+    }
+
+    fn capture(&self) -> GameState {
+        GameState {
+            matrix: self.matrix.clone(),
+            connections: self.connections.clone(),
+            piece: self.piece.clone(),
+            held: self.held.clone(),
+            piece_queue: self.piece_queue.clone(),
+            ruleset: self.ruleset.clone(),
+            stats: self.stats.clone(),
+            level_stats: self.level_stats.clone(),
+            randomizer: self.randomizer.clone(),
+            level: self.level,
+            combo: self.combo,
+            can_hold: self.can_hold,
+            prev_clear_was_fancy: self.prev_clear_was_fancy,
+            incoming_garbage: self.incoming_garbage.clone(),
+            garbage_rng: self.garbage_rng.clone(),
+            music: self.music.clone(),
+            game_over: self.game_over,
+        }
+    }
+
+    fn restore(&mut self, state: GameState) {
+        if state.music != self.music {
+            if let Some(track) = &state.music {
+                self.events.push(GameEvent::MusicChanged(track.to_string()));
+            }
+        }
+        self.history.spawn = Some(state.clone());
+        self.matrix = state.matrix;
+        self.connections = state.connections;
+        self.piece = state.piece;
+        self.held = state.held;
+        self.piece_queue = state.piece_queue;
+        self.ruleset = state.ruleset;
+        self.stats = state.stats;
+        self.level_stats = state.level_stats;
+        self.randomizer = state.randomizer;
+        self.level = state.level;
+        self.combo = state.combo;
+        self.can_hold = state.can_hold;
+        self.prev_clear_was_fancy = state.prev_clear_was_fancy;
+        self.incoming_garbage = state.incoming_garbage;
+        self.garbage_rng = state.garbage_rng;
+        self.music = state.music;
+        self.game_over = state.game_over;
+
+        // The piece starts over from where it entered the matrix
+        self.spawned_piece = self.piece.clone();
+        self.spawn_inputs = 0;
+        self.piece_inputs = 0;
+        self.pending_clear.clear();
+        self.line_clear_timer = 0;
+        self.lock_timer = 0;
+        self.gravity_timer = 0;
+        self.das_cut_timer = 0;
+        self.update_hint();
+        self.events.push(GameEvent::PieceSpawned);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::TestGame;
+    use crate::input::GameInput;
+
+    #[test]
+    fn undo_and_redo_restore_placements() {
+        let mut test = TestGame::new("practice", 3);
+        let start = test.game.matrix.clone();
+        test.tap(GameInput::HardDrop);
+        let placed = test.game.matrix.clone();
+        assert!(placed != start);
+        assert_eq!(test.game.stats.pieces_placed, 1);
+
+        test.tap(GameInput::Undo);
+        assert!(test.game.matrix == start);
+        assert_eq!(test.game.stats.pieces_placed, 0);
+        assert!(test.game.used_undo());
+
+        test.tap(GameInput::Redo);
+        assert!(test.game.matrix == placed);
+        assert_eq!(test.game.stats.pieces_placed, 1);
+    }
+
+    #[test]
+    fn placing_after_undo_clears_redo() {
+        let mut test = TestGame::new("practice", 3);
+        test.tap(GameInput::HardDrop);
+        test.tap(GameInput::Undo);
+        test.tap(GameInput::Left);
+        test.tap(GameInput::HardDrop);
+        let placed = test.game.matrix.clone();
+
+        test.tap(GameInput::Redo);
+        assert!(test.game.matrix == placed);
+        assert!(test.game.history.redo.is_empty());
+    }
+
+    #[test]
+    fn history_is_limited() {
+        let mut test = TestGame::new("practice", 3);
+        test.game.ruleset.undo_limit = 2;
+        for _ in 0..4 {
+            test.tap(GameInput::HardDrop);
+        }
+        assert_eq!(test.game.history.undo.len(), 2);
+        test.tap(GameInput::Undo);
+        test.tap(GameInput::Undo);
+        test.tap(GameInput::Undo);
+        assert_eq!(test.game.stats.pieces_placed, 2);
+    }
+}
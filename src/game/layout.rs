@@ -0,0 +1,86 @@
+use serde::Deserialize;
+use sdl2::rect::Point;
+
+/* Which point of an element its position refers to */
+#[derive(Deserialize, Clone, Copy)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /* Halves of the width and height that the anchor point is from the top left */
+    fn halves(&self) -> (i32, i32) {
+        match self {
+            Anchor::TopLeft => (0, 0),
+            Anchor::Top => (1, 0),
+            Anchor::TopRight => (2, 0),
+            Anchor::Left => (0, 1),
+            Anchor::Center => (1, 1),
+            Anchor::Right => (2, 1),
+            Anchor::BottomLeft => (0, 2),
+            Anchor::Bottom => (1, 2),
+            Anchor::BottomRight => (2, 2),
+        }
+    }
+
+    /* Get the top left corner of something of the given size whose anchor point is at the position */
+    pub fn place(&self, position: (i32, i32), width: u32, height: u32) -> Point {
+        let (x, y) = self.halves();
+        Point::new(position.0 - width as i32 * x / 2, position.1 - height as i32 * y / 2)
+    }
+
+    /* Get the top left corner of something of the given size aligned inside of an area */
+    pub fn align(&self, position: (i32, i32), area: (u32, u32), width: u32, height: u32) -> Point {
+        let (x, y) = self.halves();
+        let anchor_point = (position.0 + area.0 as i32 * x / 2, position.1 + area.1 as i32 * y / 2);
+        self.place(anchor_point, width, height)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Layout {
+    pub matrix: MatrixArea,
+    pub frame: Option<Element>,
+    pub hold: PieceBox,
+    pub preview: PieceBox,
+    pub stats: StatList,
+    pub level: (i32, i32),
+    pub next_level: Element,
+    pub gamemode_name: Element,
+}
+
+/* The matrix is scaled to fit in the area, then aligned within it by the anchor */
+#[derive(Deserialize)]
+pub struct MatrixArea {
+    pub position: (i32, i32),
+    pub size: (u32, u32),
+    pub anchor: Anchor,
+}
+
+#[derive(Deserialize)]
+pub struct Element {
+    pub position: (i32, i32),
+    pub anchor: Anchor,
+}
+
+/* Pieces are centered in square boxes, the preview stacks its boxes downward */
+#[derive(Deserialize)]
+pub struct PieceBox {
+    pub position: (usize, usize),
+    pub box_size: usize,
+    pub block_size: u32,
+}
+
+#[derive(Deserialize)]
+pub struct StatList {
+    pub position: (i32, i32),
+    pub spacing: i32,
+}
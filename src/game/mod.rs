@@ -1,24 +1,64 @@
 pub mod render;
 pub mod assets;
+pub mod layout;
+pub mod effects;
 mod randomizer;
-mod piece;
+mod rng;
+mod hash;
+pub mod piece;
+pub mod search;
+pub mod garbage;
+pub mod save;
+pub mod setup;
+mod history;
 mod configuration;
 
 use piece::*;
 use randomizer::*;
 use crate::{input::*, load_data_ron};
-use configuration::{GameMode, Ruleset, EndCondition};
+use configuration::{GameMode, GameStat, Ruleset, EndCondition};
+use search::{Heuristic, Placement};
+use garbage::IncomingGarbage;
+use rng::GameRng;
+use hash::StateHasher;
+use history::History;
 
 use std::collections::HashMap;
-use serde::Deserialize;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use enum_map::EnumMap;
 
 pub type Matrix = [Vec<PieceColor>];
+pub type Connections = [Vec<u8>];
 
-#[derive(Deserialize)]
+/* Handling settings, which are sent to the opponent in network play so they can simulate this player's game */
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Config {
     das: u32,
     arr: u32,
+    #[serde(default)]
+    socd: SocdMode,
+    #[serde(default)]
+    preserve_das: bool,
+    #[serde(default)]
+    das_cut_delay: u32,
+    #[serde(default)]
+    initial_das: bool,
+}
+
+impl Config {
+    pub fn load() -> Result<Self, String> {
+        crate::load_data(std::path::Path::new("config/config.toml"))
+    }
+}
+
+/* Simultaneous Opposing Cardinal Directions - How to resolve left and right being held at the same time */
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+pub enum SocdMode {
+    #[default]
+    Neutral, // Neither direction is applied
+    LastWins, // The most recently pressed direction is applied
+    FirstWins, // The direction that was pressed first is applied
 }
 
 pub enum MovementAction {
@@ -28,12 +68,47 @@ pub enum MovementAction {
     ShiftHorizontal(HDirection),
     None,
 }
+/* Describes a line clear so that it can be shown after the rows are gone */
+#[derive(Serialize)]
+pub struct LineClear {
+    pub rows: Vec<usize>, // Matrix rows that were cleared, including offscreen rows
+    pub blocks: Vec<Vec<PieceColor>>, // Contents of the cleared rows
+    pub spin: bool,
+    pub back_to_back: bool,
+    pub perfect_clear: bool,
+    pub combo: u32, // Consecutive clearing placements before this one
+}
+
+/* Things that happened during an update, in the order they happened.
+   Scenes drain these each update to drive effects, sounds and anything else that reacts to the game without affecting it */
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameEvent {
+    PieceSpawned,
+    Moved,
+    Rotated { kick: usize }, // 0 for a plain rotation, otherwise the 1-based index of the wall kick used
+    Held,
+    SoftDropped,
+    HardDropped,
+    Locked,
+    LinesCleared(LineClear),
+    LevelUp,
+    RulesetChanged, // The new level had ruleset modifiers
+    MusicChanged(String), // Name of the track from the sound config
+    FinesseFault, // The piece was placed with more inputs than it needed
+    GarbageReceived(u32), // Lines of garbage that entered the matrix
+    GameEnded,
+}
+
 /* All times are in microseconds (µs) */
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Stats {
     pub score: u32,
     pub time: u128,
     pub lines_cleared: u32,
     pub pieces_placed: u32,
+    pub inputs: u32,
+    pub finesse_faults: u32, // Inputs beyond the fewest that could have placed each piece
 }
 
 impl Stats {
@@ -43,6 +118,8 @@ impl Stats {
             time: 0,
             lines_cleared: 0,
             pieces_placed: 0,
+            inputs: 0,
+            finesse_faults: 0,
         }
     }
 
@@ -68,13 +145,26 @@ impl Stats {
             0.0
         }
     }
+
+    pub fn keys_per_piece(&self) -> f64 {
+        if self.pieces_placed > 0 {
+            self.inputs as f64 / self.pieces_placed as f64
+        } else {
+            0.0
+        }
+    }
 }
 
+/* Everything but the data loaded from files and the hint can be saved, see save.rs */
+#[derive(Serialize, Deserialize)]
 pub struct Game {
     matrix: Vec<Vec<PieceColor>>,
+    connections: Vec<Vec<u8>>, // Which neighbors of each locked block belong to the same piece, see piece::CONNECTED_UP
     piece: Piece,
     held: Option<Piece>,
+    #[serde(skip)]
     piece_data: HashMap<String, PieceType>,
+    #[serde(skip)]
     kick_data: HashMap<String, KickData>,
     piece_queue: Vec<Piece>,
     gamemode: GameMode,
@@ -86,27 +176,56 @@ pub struct Game {
 
     das: u128, // Delayed Auto-Shift - Time in µs that left/right must be held before auto-shift begins
     arr: u128, // Auto-Repeat Rate - Time in µs the stays in each play during auto-shift
+    socd: SocdMode,
+    preserve_das: bool, // Keep DAS charged across piece spawns while the direction is held
+    das_cut_delay: u128, // Time in µs after a spawn or hard drop before auto-shift can move the piece
+    initial_das: bool, // Apply the held direction as soon as a piece spawns
 
     /* Timer fields count upward to the above related values */
     das_timer: u128,
+    das_cut_timer: u128,
     gravity_timer: u128,
     lock_timer: u128,
+    line_clear_timer: u128,
     arr_leftover: u128, // Remainder of arr time from the previous update, should add to elapsed time
 
+    piece_inputs: u32, // Inputs used on the current piece
+    spawned_piece: Piece, // The current piece as it entered the matrix, to search for the fewest inputs it needed
+    spawn_inputs: u32, // Inputs expected before the current piece entered the matrix, 1 if it came out of hold
+    pending_clear: Vec<usize>, // Rows waiting for the line clear delay to finish before they are removed
+    #[serde(skip)]
+    events: Vec<GameEvent>, // Events since the last time they were taken
+    combo: u32,
     can_hold: bool,
     prev_clear_was_fancy: bool,
     prev_direction: HDirection,
     game_over: bool,
+    #[serde(skip)]
+    heuristic: Option<Heuristic>, // Only loaded when the gamemode shows hints
+    #[serde(skip)]
+    hint: Option<Placement>,
+    incoming_garbage: Vec<IncomingGarbage>, // Oldest first
+    garbage_rng: GameRng, // Chooses the holes in garbage, separate from the randomizer so garbage does not change the pieces
+    music: Option<String>, // Track that should be playing, so it can be started again when a saved game resumes
+    #[serde(skip)]
+    history: History, // Only kept when the ruleset is for practice
+    #[serde(default)]
+    used_undo: bool, // Runs that undid a placement are not comparable to ones that did not
 }
 
 impl Game {
     pub fn new(gamemode_name: &str) -> Result<Self, String> {
+        Self::with_settings(gamemode_name, rand::random(), Config::load()?)
+    }
+
+    /* Games with the same seed, config and inputs play out exactly the same */
+    pub fn with_settings(gamemode_name: &str, seed: u64, config: Config) -> Result<Self, String> {
         let gamemode: GameMode = load_data_ron(std::path::Path::new(&format!("data/gamemodes/{}.ron", gamemode_name)))?;
         gamemode.validate()?;
         let ruleset: Ruleset = load_data_ron(std::path::Path::new(&format!("data/rulesets/{}.ron", gamemode.initial_ruleset)))?;
-        let config: Config = crate::load_data(std::path::Path::new("config/config.toml"))?;
 
         let matrix = vec![vec![PieceColor::Empty; ruleset.matrix_width]; ruleset.matrix_height+crate::OFFSCREEN_ROWS];
+        let connections = vec![vec![0; ruleset.matrix_width]; ruleset.matrix_height+crate::OFFSCREEN_ROWS];
         let piece_data = crate::load_data(std::path::Path::new("data/piece_data.toml"))?;
         let kick_data = crate::load_data(std::path::Path::new("data/wall_kick_data.toml"))?;
         validate_data(&piece_data, &kick_data, &ruleset.piece_list)?;
@@ -116,7 +235,7 @@ impl Game {
             Some(x) => x,
             None => ruleset.randomizer,
         };
-        let mut randomizer = Randomizer::new(ruleset.piece_list.clone(), starting_randomizer);
+        let mut randomizer = Randomizer::new(ruleset.piece_list.clone(), starting_randomizer, seed);
         let mut piece_queue = randomizer.generate_pieces(&ruleset.cannot_start_with, &piece_data);
         randomizer.style = ruleset.randomizer;
         extend_queue(&mut piece_queue, ruleset.preview_count, &piece_data, &mut randomizer);
@@ -126,8 +245,21 @@ impl Game {
 
         let level_stats = Stats::new();
 
-        Ok(Self {
+        let heuristic = match gamemode.show_hint {
+            true => Some(Heuristic::load()?),
+            false => None,
+        };
+
+        let mut events = vec![GameEvent::PieceSpawned];
+        if let Some(track) = &gamemode.music {
+            events.push(GameEvent::MusicChanged(track.to_string()));
+        }
+        let music = gamemode.music.clone();
+
+        let mut game = Self {
             matrix,
+            connections,
+            spawned_piece: piece.clone(),
             piece,
             held: None,
             piece_data,
@@ -140,43 +272,128 @@ impl Game {
             randomizer,
             level: 1,
 
-            // Config values are in milliseconds, must be converted to microseconds
-            das: config.das as u128 * 1000,
-            arr: config.arr as u128 * 1000,
+            das: 0,
+            arr: 0,
+            socd: SocdMode::default(),
+            preserve_das: false,
+            das_cut_delay: 0,
+            initial_das: false,
 
             das_timer: 0,
+            das_cut_timer: 0,
             gravity_timer: 0,
             lock_timer: 0,
+            line_clear_timer: 0,
             arr_leftover: 0,
 
+            piece_inputs: 0,
+            spawn_inputs: 0,
+            pending_clear: Vec::new(),
+            events,
+            combo: 0,
             can_hold: true,
             prev_clear_was_fancy: false,
             prev_direction: HDirection::None,
             game_over: false,
-        })
+            heuristic,
+            hint: None,
+            incoming_garbage: Vec::new(),
+            garbage_rng: GameRng::new(!seed),
+            music,
+            history: History::default(),
+            used_undo: false,
+        };
+        game.apply_config(&config);
+        game.update_hint();
+        game.record_spawn();
+        Ok(game)
     }
 
-    pub fn update(&mut self, input: &mut EnumMap<GameInput, bool>, elapsed: u128) {
-        if self.gamemode.end_condition.check(&self.stats) {
-            return;
+    fn apply_config(&mut self, config: &Config) {
+        // Config values are in milliseconds, must be converted to microseconds
+        self.das = config.das as u128 * 1000;
+        self.arr = config.arr as u128 * 1000;
+        self.socd = config.socd;
+        self.preserve_das = config.preserve_das;
+        self.das_cut_delay = config.das_cut_delay as u128 * 1000;
+        self.initial_das = config.initial_das;
+    }
+
+    /* Simulate up to each input change in turn so that timers are exact between inputs */
+    pub fn update(&mut self, input: &mut EnumMap<GameInput, bool>, press_times: &mut EnumMap<GameInput, u32>, input_changes: &[InputChange<GameInput>], elapsed: u128) {
+        let mut step_start = 0;
+        for change in input_changes {
+            let time = change.time.clamp(step_start, elapsed);
+            self.step(input, press_times, time - step_start);
+            step_start = time;
+            self.count_input(input, change);
+            apply_input_change(input, press_times, change);
+        }
+        self.step(input, press_times, elapsed - step_start);
+    }
+
+    fn count_input(&mut self, input: &EnumMap<GameInput, bool>, change: &InputChange<GameInput>) {
+        let is_new_press = change.pressed && !input[change.input];
+        if is_new_press && !matches!(change.input, GameInput::Reset | GameInput::Pause | GameInput::Undo | GameInput::Redo) && !self.game_over {
+            self.piece_inputs += 1;
+            self.stats.inputs += 1;
+            self.level_stats.inputs += 1;
+        }
+    }
+
+    fn step(&mut self, input: &mut EnumMap<GameInput, bool>, press_times: &EnumMap<GameInput, u32>, elapsed: u128) {
+        // Undo comes before the game over check so that a top out can be taken back
+        if input[GameInput::Undo] {
+            input[GameInput::Undo] = false;
+            self.undo();
+        }
+        if input[GameInput::Redo] {
+            input[GameInput::Redo] = false;
+            self.redo();
         }
         if self.game_over {
             return;
         }
+        if self.gamemode.end_condition.check(&self.stats) {
+            self.end_game();
+            return;
+        }
         self.stats.time += elapsed;
         self.level_stats.time += elapsed;
-        let (movement_action, rotation_action) = read_inputs(input);
+        self.das_cut_timer += elapsed;
+        for garbage in self.incoming_garbage.iter_mut() {
+            garbage.delay = garbage.delay.saturating_sub(elapsed);
+        }
+        let (movement_action, rotation_action) = read_inputs(input, press_times, self.socd);
+        let held_direction = resolve_direction(input, press_times, GameInput::Left, GameInput::Right, self.socd);
         let mut placed_piece = false;
 
+        /* The next piece waits for the line clear delay, then enters the collapsed matrix */
+        if !self.pending_clear.is_empty() {
+            self.line_clear_timer += elapsed;
+            if self.line_clear_timer < self.ruleset.line_clear_delay {
+                return;
+            }
+            self.remove_cleared_rows();
+            self.piece.update_ghost(&self.matrix);
+            self.spawn_movement(held_direction);
+        }
+
         match movement_action {
             MovementAction::HardDrop => {
                 input[GameInput::HardDrop] = false;
                 self.piece.hard_drop();
+                self.das_cut_timer = 0;
+                self.events.push(GameEvent::HardDropped);
                 placed_piece = true;
             }
             MovementAction::InstantDrop => {
                 input[GameInput::InstantDrop] = false;
+                if self.piece.position.row != self.piece.ghost_position {
+                    self.events.push(GameEvent::SoftDropped);
+                }
                 self.piece.hard_drop();
+                self.das_cut_timer = 0;
             }
             MovementAction::Horizontal(direction) => {
                 self.handle_piece_movement(elapsed, direction);
@@ -196,16 +413,17 @@ impl Game {
                 input[GameInput::RotateCW] = false;
                 input[GameInput::RotateCCW] = false;
                 input[GameInput::Rotate180] = false;
-                if self.piece.rotate(&self.matrix, &self.kick_data, rotation_action) {
+                if let Some(kick) = self.piece.rotate(&self.matrix, &self.kick_data, rotation_action) {
                     /* Reduce the lock timer after a successful rotation to make spins easier */
                     self.lock_timer = std::cmp::max(0, self.lock_timer as i128 - self.ruleset.lock_delay as i128/4) as u128;
+                    self.events.push(GameEvent::Rotated { kick });
                 }
             }
         }
 
         if self.ruleset.hold_enabled && input[GameInput::Hold] {
             input[GameInput::Hold] = false;
-            self.hold_piece();
+            self.hold_piece(held_direction);
         }
 
         self.gravity(elapsed, input[GameInput::SoftDrop]);
@@ -218,12 +436,27 @@ impl Game {
         }
 
         if placed_piece {
+            let faults = if self.tracks_finesse() { self.finesse_faults() } else { 0 };
+            if faults > 0 {
+                self.stats.finesse_faults += faults;
+                self.level_stats.finesse_faults += faults;
+                self.events.push(GameEvent::FinesseFault);
+            }
+            if self.gamemode.finesse_retry && (faults > 0 || !self.is_on_hint()) {
+                self.retry_piece(held_direction);
+                return;
+            }
+
             let bonus = self.piece.check_bonus(&self.matrix);
-            self.piece.lock(&mut self.matrix);
-            self.game_over = self.check_loss();
+            self.piece.lock(&mut self.matrix, &mut self.connections);
+            self.events.push(GameEvent::Locked);
+            self.record_placement();
+            if self.check_loss() {
+                self.end_game();
+            }
             self.lock_timer = 0;
             self.gravity_timer = 0;
-            self.direction_change(HDirection::None);
+            self.piece_inputs = 0;
             self.can_hold = true;
             self.stats.pieces_placed += 1;
             self.level_stats.pieces_placed += 1;
@@ -236,32 +469,79 @@ impl Game {
             }
             extend_queue(&mut self.piece_queue, self.ruleset.preview_count, &self.piece_data, &mut self.randomizer);
             self.piece = next_piece(&mut self.piece_queue, &self.matrix);
+            // Garbage can push blocks into the space where pieces enter
+            if self.piece.overlaps(&self.matrix) {
+                self.end_game();
+            }
+            if self.pending_clear.is_empty() {
+                self.spawn_movement(held_direction);
+            }
         }
     }
 
     fn handle_piece_movement(&mut self, elapsed: u128, direction: HDirection) {
         if self.prev_direction != direction {
-            self.piece.movement(&self.matrix, direction, VDirection::None);
+            self.shift(direction);
             self.direction_change(direction);
         } else {
             self.das_timer += elapsed;
-            if self.das_timer >= self.das {
+            if self.das_timer >= self.das && self.das_cut_timer >= self.das_cut_delay {
                 let time = elapsed + self.arr_leftover;
                 self.auto_shift(direction, time, self.arr);
             }
         }
     }
 
+    /* Decide what happens to a held direction when a new piece enters the matrix */
+    fn spawn_movement(&mut self, held_direction: HDirection) {
+        self.events.push(GameEvent::PieceSpawned);
+        self.spawned_piece = self.piece.clone();
+        self.spawn_inputs = 0;
+        self.update_hint();
+        self.record_spawn();
+        self.das_cut_timer = 0;
+        self.arr_leftover = 0;
+        if !self.preserve_das || self.prev_direction != held_direction {
+            self.direction_change(HDirection::None);
+        }
+        if !self.initial_das || held_direction == HDirection::None {
+            return;
+        }
+
+        if self.prev_direction != held_direction {
+            self.shift(held_direction);
+            self.direction_change(held_direction);
+        } else if self.das_timer >= self.das {
+            // Initial DAS ignores the DAS cut delay, the charge is spent immediately
+            self.auto_shift(held_direction, self.arr + 1, self.arr);
+        }
+    }
+
+    fn shift(&mut self, direction: HDirection) -> bool {
+        let moved = self.piece.movement(&self.matrix, direction, VDirection::None);
+        if moved {
+            self.events.push(GameEvent::Moved);
+        }
+        moved
+    }
+
     fn auto_shift(&mut self, direction: HDirection, time: u128, arr: u128) {
         let mut leftover = time;
+        let mut moved = false;
         while leftover > arr {
             if !self.piece.movement(&self.matrix, direction, VDirection::None) {
                 self.arr_leftover = 0;
-                return;
+                leftover = 0;
+                break;
             }
+            moved = true;
             leftover -= arr;
         }
         self.arr_leftover = leftover;
+        // Only one event for each update, the piece can move many times at once with a low ARR
+        if moved {
+            self.events.push(GameEvent::Moved);
+        }
     }
 
     fn gravity(&mut self, elapsed: u128, speed_up: bool) {
@@ -276,28 +556,260 @@ impl Game {
             if !self.piece.movement(&self.matrix, HDirection::None, VDirection::Down) {
                 return;
             }
+            if speed_up {
+                self.events.push(GameEvent::SoftDropped);
+            }
         }
     }
 
     fn handle_line_clears(&mut self, bonus: bool) {
         let cleared_lines = filled_rows(&mut self.matrix);
-        if !cleared_lines.is_empty() {
-            self.update_score(cleared_lines.len() as u32, bonus);
-            self.stats.lines_cleared += cleared_lines.len() as u32;
-            self.level_stats.lines_cleared += cleared_lines.len() as u32;
-            remove_rows(&mut self.matrix, cleared_lines);
+        if cleared_lines.is_empty() {
+            self.combo = 0;
+            self.receive_garbage();
+            return;
+        }
+
+        let back_to_back = self.update_score(cleared_lines.len() as u32, bonus);
+        self.stats.lines_cleared += cleared_lines.len() as u32;
+        self.level_stats.lines_cleared += cleared_lines.len() as u32;
+
+        let remaining_blocks = self.matrix.iter().flatten().filter(|x| **x != PieceColor::Empty).count();
+        self.events.push(GameEvent::LinesCleared(LineClear {
+            blocks: cleared_lines.iter().map(|row| self.matrix[*row].clone()).collect(),
+            rows: cleared_lines.clone(),
+            spin: bonus,
+            back_to_back,
+            perfect_clear: remaining_blocks == cleared_lines.len() * self.matrix[0].len(),
+            combo: self.combo,
+        }));
+        self.combo += 1;
+
+        self.pending_clear = cleared_lines;
+        self.line_clear_timer = 0;
+        if self.ruleset.line_clear_delay == 0 {
+            self.remove_cleared_rows();
+        }
+    }
+
+    /* Add garbage to the meter, it enters the matrix after the delay when a piece is placed without clearing lines */
+    pub fn queue_garbage(&mut self, lines: u32, delay: u128) {
+        if lines > 0 {
+            self.incoming_garbage.push(IncomingGarbage { lines, delay });
+        }
+    }
+
+    /* Remove lines from the meter, oldest first. Returns the lines that were left over */
+    pub fn cancel_garbage(&mut self, mut lines: u32) -> u32 {
+        while lines > 0 && !self.incoming_garbage.is_empty() {
+            let garbage = &mut self.incoming_garbage[0];
+            let cancelled = std::cmp::min(lines, garbage.lines);
+            garbage.lines -= cancelled;
+            lines -= cancelled;
+            if garbage.lines == 0 {
+                self.incoming_garbage.remove(0);
+            }
         }
+        lines
+    }
+
+    /* Lines in the meter that can enter the matrix, and lines that are still waiting */
+    pub fn incoming_garbage(&self) -> (u32, u32) {
+        self.incoming_garbage.iter().fold((0, 0), |(ready, waiting), x| match x.delay {
+            0 => (ready + x.lines, waiting),
+            _ => (ready, waiting + x.lines),
+        })
+    }
+
+    /* Push the matrix up and fill the bottom with the garbage that is ready, leaving one hole in each attack */
+    fn receive_garbage(&mut self) {
+        let mut received = 0;
+        while !self.incoming_garbage.is_empty() && self.incoming_garbage[0].delay == 0 {
+            let garbage = self.incoming_garbage.remove(0);
+            let width = self.matrix[0].len();
+            let hole = self.garbage_rng.gen_range(0..width);
+            for _ in 0..garbage.lines {
+                // Blocks pushed off the top of the matrix top out the player
+                if self.matrix[0].iter().any(|x| *x != PieceColor::Empty) {
+                    self.end_game();
+                }
+                self.matrix.remove(0);
+                self.connections.remove(0);
+                let mut row = vec![PieceColor::Gray; width];
+                row[hole] = PieceColor::Empty;
+                self.matrix.push(row);
+                self.connections.push(vec![0; width]);
+            }
+            received += garbage.lines;
+        }
+        if received > 0 {
+            self.events.push(GameEvent::GarbageReceived(received));
+        }
+    }
+
+    fn remove_cleared_rows(&mut self) {
+        let cleared_lines = std::mem::take(&mut self.pending_clear);
+        cut_connections(&mut self.connections, &cleared_lines);
+        remove_rows(&mut self.matrix, &cleared_lines, PieceColor::Empty);
+        remove_rows(&mut self.connections, &cleared_lines, 0);
+    }
+
+    /* The highest scoring placement of the current piece, or of the piece that holding would bring out */
+    pub fn best_placement(&self, heuristic: &Heuristic) -> Option<Placement> {
+        let mut candidates = search::placements(&self.piece, &self.matrix, &self.kick_data, self.has_instant_gravity());
+        if self.ruleset.hold_enabled && self.can_hold {
+            let swapped = match &self.held {
+                Some(held) => Some(held.clone()),
+                None => self.piece_queue.last().cloned(),
+            };
+            if let Some(mut piece) = swapped {
+                piece.reset_position(&self.matrix);
+                for mut placement in search::placements(&piece, &self.matrix, &self.kick_data, self.has_instant_gravity()) {
+                    placement.inputs.insert(0, GameInput::Hold);
+                    candidates.push(placement);
+                }
+            }
+        }
+
+        let mut best: Option<Placement> = None;
+        for mut placement in candidates {
+            placement.score = heuristic.evaluate(&self.matrix, &placement.cells);
+            // Ties go to the placement found first, which needs fewer inputs
+            if best.as_ref().is_none_or(|x| placement.score > x.score) {
+                best = Some(placement);
+            }
+        }
+        best
+    }
+
+    fn update_hint(&mut self) {
+        self.hint = match &self.heuristic {
+            Some(heuristic) => self.best_placement(heuristic),
+            None => None,
+        };
+    }
+
+    /* A summary of everything that affects how the game plays out, compared between machines to find desyncs */
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::default();
+        for row in self.matrix.iter() {
+            hasher.write(&row.iter().map(|x| *x as u8).collect::<Vec<_>>());
+        }
+        hasher.write_u64(self.held.is_some() as u64);
+        for piece in std::iter::once(&self.piece).chain(self.held.iter()).chain(self.piece_queue.iter()) {
+            hasher.write_str(&piece.name);
+        }
+        for value in [self.piece.position.col as i64, self.piece.position.row as i64, self.piece.rotation() as i64] {
+            hasher.write_u64(value as u64);
+        }
+        for value in [self.stats.score, self.stats.lines_cleared, self.stats.pieces_placed] {
+            hasher.write_u64(value as u64);
+        }
+        hasher.write_u64(self.level as u64);
+        hasher.write_u64(self.game_over as u64);
+        hasher.write_u64(self.can_hold as u64);
+        for timer in [self.lock_timer, self.gravity_timer, self.line_clear_timer] {
+            hasher.write_u64(timer as u64);
+        }
+        hasher.write_u64(self.randomizer.rng_state());
+        hasher.write_u64(self.garbage_rng.state());
+        for garbage in self.incoming_garbage.iter() {
+            hasher.write_u64(garbage.lines as u64);
+            hasher.write_u64(garbage.delay as u64);
+        }
+        hasher.finish()
+    }
+
+    /* Events that happened since this was last called */
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /* True while the line clear delay is holding back the next piece */
+    pub fn is_clearing_lines(&self) -> bool {
+        !self.pending_clear.is_empty()
+    }
+
+    pub fn layout_name(&self) -> &str {
+        &self.gamemode.layout
+    }
+
+    pub fn background(&self) -> &Option<String> {
+        &self.gamemode.background
     }
 
     pub fn get_preview_pieces(&self) -> &[Piece] {
         &self.piece_queue[self.piece_queue.len()-self.ruleset.preview_count..]
     }
 
-    fn hold_piece(&mut self) {
+    /* Read only access for players other than the keyboard, such as bots */
+    pub fn matrix(&self) -> &Matrix {
+        &self.matrix
+    }
+
+    pub fn current_piece(&self) -> &Piece {
+        &self.piece
+    }
+
+    pub fn held_piece(&self) -> Option<&Piece> {
+        self.held.as_ref()
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn can_hold(&self) -> bool {
+        self.ruleset.hold_enabled && self.can_hold
+    }
+
+    pub fn combo(&self) -> u32 {
+        self.combo
+    }
+
+    /* True if the next fancy clear would be back-to-back */
+    pub fn back_to_back(&self) -> bool {
+        self.prev_clear_was_fancy
+    }
+
+    pub fn kick_data(&self) -> &HashMap<String, KickData> {
+        &self.kick_data
+    }
+
+    /* With zero gravity pieces fall to the bottom as soon as they move */
+    pub fn has_instant_gravity(&self) -> bool {
+        self.ruleset.gravity == 0
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.game_over
+    }
+
+    pub fn used_undo(&self) -> bool {
+        self.used_undo
+    }
+
+    /* Fraction of DAS that has been charged in the currently held direction */
+    pub fn das_charge(&self) -> f64 {
+        if self.prev_direction == HDirection::None {
+            0.0
+        } else if self.das == 0 {
+            1.0
+        } else {
+            f64::min(self.das_timer as f64 / self.das as f64, 1.0)
+        }
+    }
+
+    fn hold_piece(&mut self, held_direction: HDirection) {
         if !self.can_hold {
             return;
         }
         self.can_hold = false;
+        self.events.push(GameEvent::Held);
         self.piece.reset_position(&self.matrix);
         match &mut self.held {
             Some(held) => {
@@ -311,11 +823,50 @@ impl Game {
         }
         self.gravity_timer = 0;
         self.lock_timer = 0;
-        self.direction_change(HDirection::None);
         self.piece.update_ghost(&self.matrix);
+        self.spawn_movement(held_direction);
+        self.spawn_inputs = 1;
+    }
+
+    /* The search behind finesse faults is only run for gamemodes that show or retry them */
+    fn tracks_finesse(&self) -> bool {
+        self.gamemode.finesse_retry || self.gamemode.displayed_stats.iter().any(|x| matches!(x, GameStat::FinesseFaults))
+    }
+
+    /* Extra inputs used to place the current piece where it is now.
+       Holding a direction until it auto-shifts to the wall counts as one input, as does a soft drop, so only taps are faults */
+    fn finesse_faults(&self) -> u32 {
+        let mut cells = self.piece.landing_cells();
+        cells.sort_unstable();
+        let fewest = search::placements(&self.spawned_piece, &self.matrix, &self.kick_data, self.has_instant_gravity()).into_iter()
+            .find(|x| x.cells == cells)
+            .map(|x| x.inputs.len() as u32);
+        match fewest {
+            Some(fewest) => self.piece_inputs.saturating_sub(fewest + self.spawn_inputs),
+            // Placements the search cannot reach, such as ones that need gravity to fall into place, are never faults
+            None => 0,
+        }
+    }
+
+    fn is_on_hint(&self) -> bool {
+        let mut cells = self.piece.landing_cells();
+        cells.sort_unstable();
+        self.hint.as_ref().is_none_or(|x| x.cells == cells)
+    }
+
+    /* Put the piece back where it entered the matrix so that it can be placed again */
+    fn retry_piece(&mut self, held_direction: HDirection) {
+        self.piece = self.spawned_piece.clone();
+        self.piece.update_ghost(&self.matrix);
+        self.piece_inputs = 0;
+        self.lock_timer = 0;
+        self.gravity_timer = 0;
+        self.das_cut_timer = 0;
+        self.spawn_movement(held_direction);
     }
 
-    fn update_score(&mut self, cleared_rows: u32, bonus: bool) {
+    /* Returns true if the clear was back-to-back */
+    fn update_score(&mut self, cleared_rows: u32, bonus: bool) -> bool {
         let exponent = if bonus {
             cleared_rows + 1
         } else {
@@ -324,7 +875,8 @@ impl Game {
         let mut points = 100 * u32::pow(2, exponent);
 
         let fancy = cleared_rows >= 4 || (bonus && cleared_rows >= 2);
-        if self.prev_clear_was_fancy && fancy {
+        let back_to_back = self.prev_clear_was_fancy && fancy;
+        if back_to_back {
             points += (points as f64 * 0.5) as u32;
         }
         points = self.ruleset.score_multiplier.apply(points, self.level);
@@ -332,6 +884,7 @@ impl Game {
         self.prev_clear_was_fancy = fancy;
         self.stats.score += points;
         self.level_stats.score += points;
+        back_to_back
     }
 
     /* Lose if the piece is placed entirely offscreen */
@@ -351,8 +904,19 @@ impl Game {
         self.prev_direction = direction;
     }
 
+    fn end_game(&mut self) {
+        if !self.game_over {
+            self.game_over = true;
+            self.events.push(GameEvent::GameEnded);
+        }
+    }
+
     fn level_up(&mut self) {
         self.level += 1;
+        self.events.push(GameEvent::LevelUp);
+        if self.gamemode.level_list.get(self.level - 2).is_some_and(|x| !x.is_empty()) {
+            self.events.push(GameEvent::RulesetChanged);
+        }
         self.level_stats = Stats::next_level(&self.level_stats, &self.ruleset.level_up_condition);
         let commands = self.gamemode.level_up(&mut self.ruleset, self.level).unwrap();
         for command in commands {
@@ -360,13 +924,17 @@ impl Game {
                 configuration::Command::RegeneratePieces => self.change_randomizer(),
                 configuration::Command::ResizeMatrix => self.adjust_matrix_size(),
                 configuration::Command::ClearMatrix => self.clear_matrix(),
-                configuration::Command::End => self.game_over = true,
+                configuration::Command::PlayMusic(track) => {
+                    self.music = Some(track.clone());
+                    self.events.push(GameEvent::MusicChanged(track));
+                }
+                configuration::Command::End => self.end_game(),
             }
         }
     }
 
     fn change_randomizer(&mut self) {
-        self.randomizer = Randomizer::new(self.ruleset.piece_list.clone(), self.ruleset.randomizer);
+        self.randomizer.reset(self.ruleset.piece_list.clone(), self.ruleset.randomizer);
         // Remove pieces in the piece queue so that the newer randomizer takes effect sooner
         // Leave some pieces to reduce jarring changes
         let leftovers = std::cmp::min(3, self.piece_queue.len());
@@ -375,34 +943,11 @@ impl Game {
     }
 
     fn adjust_matrix_size(&mut self) {
-        let mut new_matrix = vec![vec![PieceColor::Empty; self.ruleset.matrix_width]; self.ruleset.matrix_height+crate::OFFSCREEN_ROWS];
-
-        // if the new matrix is larger than the old, the stack should be centered horizontally
-        let new_center = (new_matrix[0].len()/2) as i32;
-        let old_center = (self.matrix[0].len()/2) as i32;
-        let left_edge = std::cmp::max(0, new_center - old_center) as usize;
-
-        // if it is smaller, columns should be cut off each side evenly
-        let horizontal_change = new_matrix[0].len() as i32 - self.matrix[0].len() as i32;
-        let (cut_from_left, cut_from_right) = if horizontal_change >= 0 {
-            (0, 0)
-        } else if horizontal_change % 2 == 0 {
-            ((horizontal_change.abs()/2) as usize, (horizontal_change.abs()/2) as usize)
-        } else {
-            ((horizontal_change.abs()/2) as usize, (horizontal_change.abs()/2 + 1) as usize)
-        };
-
-        for row in new_matrix.iter_mut().rev() {
-            let mut old_row = match self.matrix.pop() {
-                Some(x) => x,
-                None => break,
-            };
-            let len = old_row.len();
-            old_row.drain(len-cut_from_right..);
-            old_row.drain(0..cut_from_left);
-            row.splice(left_edge..left_edge+old_row.len(), old_row);
-        }
-        self.matrix = new_matrix;
+        self.remove_cleared_rows();
+        let (width, height) = (self.ruleset.matrix_width, self.ruleset.matrix_height+crate::OFFSCREEN_ROWS);
+        self.matrix = resize_matrix(std::mem::take(&mut self.matrix), width, height, PieceColor::Empty);
+        self.connections = resize_matrix(std::mem::take(&mut self.connections), width, height, 0);
+        cut_edge_connections(&mut self.connections);
         self.piece.reset_position(&self.matrix);
         if let Some(held) = &mut self.held {
             held.reset_position(&self.matrix);
@@ -410,11 +955,74 @@ impl Game {
     }
 
     fn clear_matrix(&mut self) {
+        self.remove_cleared_rows();
         for row in &mut self.matrix {
             for col in row {
                 *col = PieceColor::Empty;
             }
         }
+        for row in &mut self.connections {
+            for col in row {
+                *col = 0;
+            }
+        }
+    }
+}
+
+fn resize_matrix<T: Copy>(mut matrix: Vec<Vec<T>>, width: usize, height: usize, empty: T) -> Vec<Vec<T>> {
+    let mut new_matrix = vec![vec![empty; width]; height];
+
+    // if the new matrix is larger than the old, the stack should be centered horizontally
+    let new_center = (new_matrix[0].len()/2) as i32;
+    let old_center = (matrix[0].len()/2) as i32;
+    let left_edge = std::cmp::max(0, new_center - old_center) as usize;
+
+    // if it is smaller, columns should be cut off each side evenly
+    let horizontal_change = new_matrix[0].len() as i32 - matrix[0].len() as i32;
+    let (cut_from_left, cut_from_right) = if horizontal_change >= 0 {
+        (0, 0)
+    } else if horizontal_change % 2 == 0 {
+        ((horizontal_change.abs()/2) as usize, (horizontal_change.abs()/2) as usize)
+    } else {
+        ((horizontal_change.abs()/2) as usize, (horizontal_change.abs()/2 + 1) as usize)
+    };
+
+    for row in new_matrix.iter_mut().rev() {
+        let mut old_row = match matrix.pop() {
+            Some(x) => x,
+            None => break,
+        };
+        let len = old_row.len();
+        old_row.drain(len-cut_from_right..);
+        old_row.drain(0..cut_from_left);
+        row.splice(left_edge..left_edge+old_row.len(), old_row);
+    }
+    new_matrix
+}
+
+/* Disconnect blocks from the rows that are about to be cleared */
+fn cut_connections(connections: &mut Connections, cleared: &[usize]) {
+    for row in cleared.iter() {
+        for col in 0..connections[0].len() {
+            if *row > 0 {
+                connections[row-1][col] &= !CONNECTED_DOWN;
+            }
+            if row+1 < connections.len() {
+                connections[row+1][col] &= !CONNECTED_UP;
+            }
+        }
+    }
+}
+
+/* Disconnect blocks from anything that was cut off the edges of a resized matrix */
+fn cut_edge_connections(connections: &mut Connections) {
+    let last_col = connections[0].len() - 1;
+    for row in connections.iter_mut() {
+        row[0] &= !CONNECTED_LEFT;
+        row[last_col] &= !CONNECTED_RIGHT;
+    }
+    for col in connections[0].iter_mut() {
+        *col &= !CONNECTED_UP;
     }
 }
 
@@ -451,11 +1059,11 @@ fn filled_rows(matrix: &mut Matrix) -> Vec<usize> {
     cleared
 }
 
-fn remove_rows(matrix: &mut Matrix, remove: Vec<usize>) {
+fn remove_rows<T: Copy>(matrix: &mut [Vec<T>], remove: &[usize], empty: T) {
     for row in remove.iter() {
         // Empty the row
         for col in 0..matrix[0].len() {
-            matrix[*row][col] = PieceColor::Empty;
+            matrix[*row][col] = empty;
         }
         // Swap the row upward
         for current in (1..=*row).rev() {
@@ -464,7 +1072,7 @@ fn remove_rows(matrix: &mut Matrix, remove: Vec<usize>) {
     }
 }
 
-fn read_inputs(input: &EnumMap<GameInput, bool>) -> (MovementAction, RotationAction) {
+fn read_inputs(input: &EnumMap<GameInput, bool>, press_times: &EnumMap<GameInput, u32>, socd: SocdMode) -> (MovementAction, RotationAction) {
     /* HardDrop and InstantDrop return to disallow rotation with those movements */
     use crate::input::GameInput::*;
     if input[HardDrop] {
@@ -474,12 +1082,13 @@ fn read_inputs(input: &EnumMap<GameInput, bool>) -> (MovementAction, RotationAct
         return (MovementAction::InstantDrop, RotationAction::None);
     }
 
-    let movement_action = match (input[Left], input[Right], input[ShiftLeft], input[ShiftRight]) {
-        (_, _, true, false) => MovementAction::ShiftHorizontal(HDirection::Left),
-        (_, _, false, true) => MovementAction::ShiftHorizontal(HDirection::Right),
-        (true, false, _, _) => MovementAction::Horizontal(HDirection::Left),
-        (false, true, _, _) => MovementAction::Horizontal(HDirection::Right),
-        _ => MovementAction::None,
+    /* Shifts take priority, if they cancel each other out fall back to regular movement */
+    let movement_action = match resolve_direction(input, press_times, ShiftLeft, ShiftRight, socd) {
+        HDirection::None => match resolve_direction(input, press_times, Left, Right, socd) {
+            HDirection::None => MovementAction::None,
+            direction => MovementAction::Horizontal(direction),
+        }
+        direction => MovementAction::ShiftHorizontal(direction),
     };
 
     let rotation_action = match (input[RotateCW], input[RotateCCW], input[Rotate180]) {
@@ -491,6 +1100,26 @@ fn read_inputs(input: &EnumMap<GameInput, bool>) -> (MovementAction, RotationAct
     (movement_action, rotation_action)
 }
 
+fn resolve_direction(input: &EnumMap<GameInput, bool>, press_times: &EnumMap<GameInput, u32>, left: GameInput, right: GameInput, socd: SocdMode) -> HDirection {
+    match (input[left], input[right]) {
+        (true, false) => HDirection::Left,
+        (false, true) => HDirection::Right,
+        (true, true) => {
+            // Presses with the same timestamp are treated as truly simultaneous
+            let left_time = press_times[left];
+            let right_time = press_times[right];
+            match socd {
+                SocdMode::LastWins if left_time > right_time => HDirection::Left,
+                SocdMode::LastWins if right_time > left_time => HDirection::Right,
+                SocdMode::FirstWins if left_time < right_time => HDirection::Left,
+                SocdMode::FirstWins if right_time < left_time => HDirection::Right,
+                _ => HDirection::None,
+            }
+        }
+        (false, false) => HDirection::None,
+    }
+}
+
 /* Checks that all kick tables in the piece data are found in the wall kick data */
 // Only checks the first ruleset's piece list!
 fn validate_data(piece_data: &HashMap<String, PieceType>, wall_kick_data: &HashMap<String, KickData>, piece_list: &[String]) -> Result<(), String> {
@@ -512,3 +1141,87 @@ fn validate_data(piece_data: &HashMap<String, PieceType>, wall_kick_data: &HashM
 
     Ok(())
 }
+
+/* A game with fixed handling settings for tests, so they do not depend on config/config.toml */
+#[cfg(test)]
+pub struct TestGame {
+    pub game: Game,
+    inputs: EnumMap<GameInput, bool>,
+    press_times: EnumMap<GameInput, u32>,
+}
+
+#[cfg(test)]
+impl TestGame {
+    pub const TICK: u128 = 1000; // µs simulated by each update
+
+    pub fn new(gamemode_name: &str, seed: u64) -> Self {
+        let config = Config { das: 100, arr: 0, socd: SocdMode::Neutral, preserve_das: false, das_cut_delay: 0, initial_das: false };
+        Self {
+            game: Game::with_settings(gamemode_name, seed, config).unwrap(),
+            inputs: EnumMap::default(),
+            press_times: EnumMap::default(),
+        }
+    }
+
+    pub fn update(&mut self, input_changes: &[InputChange<GameInput>]) {
+        self.game.update(&mut self.inputs, &mut self.press_times, input_changes, Self::TICK);
+    }
+
+    /* Press and release the input, then give the next piece time to spawn */
+    pub fn tap(&mut self, input: GameInput) {
+        for pressed in [true, false] {
+            self.update(&[InputChange { input, pressed, timestamp: 0, time: 0 }]);
+        }
+        for _ in 0..10 {
+            self.update(&[]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Taps of a few inputs at fixed ticks, enough to place pieces with holds and rotations */
+    fn scripted_inputs(tick: u32) -> Vec<InputChange<GameInput>> {
+        let inputs = [GameInput::Left, GameInput::RotateCW, GameInput::Hold, GameInput::Right, GameInput::Rotate180, GameInput::HardDrop];
+        let input = inputs[(tick / 40) as usize % inputs.len()];
+        match tick % 40 {
+            0 => vec![InputChange { input, pressed: true, timestamp: tick, time: 0 }],
+            20 => vec![InputChange { input, pressed: false, timestamp: tick, time: 0 }],
+            _ => Vec::new(),
+        }
+    }
+
+    fn play(seed: u64) -> (Game, Vec<u64>) {
+        let mut test = TestGame::new("marathon", seed);
+        let hashes = (0..5000).map(|tick| {
+            test.update(&scripted_inputs(tick));
+            test.game.state_hash()
+        }).collect();
+        (test.game, hashes)
+    }
+
+    #[test]
+    fn same_seed_and_inputs_play_out_the_same() {
+        let (game, hashes) = play(7);
+        assert!(game.stats.pieces_placed > 10);
+        assert!(hashes == play(7).1);
+        assert!(hashes != play(8).1);
+    }
+
+    #[test]
+    fn cancel_garbage_removes_oldest_lines_first() {
+        let mut game = TestGame::new("versus", 0).game;
+        game.queue_garbage(2, 0);
+        game.queue_garbage(3, 1000);
+        game.queue_garbage(0, 0);
+        assert_eq!(game.incoming_garbage(), (2, 3));
+
+        assert_eq!(game.cancel_garbage(3), 0);
+        assert_eq!(game.incoming_garbage(), (0, 2));
+        assert_eq!(game.cancel_garbage(4), 2);
+        assert_eq!(game.incoming_garbage(), (0, 0));
+        assert_eq!(game.cancel_garbage(1), 1);
+    }
+}
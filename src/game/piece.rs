@@ -1,10 +1,10 @@
-use super::Matrix;
+use super::{Matrix, Connections};
 
 use std::{cmp::{max, min}, collections::HashMap};
 use rand::{distributions::Standard, prelude::Distribution};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, PartialEq, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum PieceColor {
     Empty,
     Cyan,
@@ -18,6 +18,22 @@ pub enum PieceColor {
     ColorCount
 }
 
+impl PieceColor {
+    pub fn from_index(index: usize) -> Self {
+        match index {
+            1 => PieceColor::Cyan,
+            2 => PieceColor::Magenta,
+            3 => PieceColor::Yellow,
+            4 => PieceColor::Blue,
+            5 => PieceColor::Orange,
+            6 => PieceColor::Green,
+            7 => PieceColor::Red,
+            8 => PieceColor::Gray,
+            _ => PieceColor::Empty,
+        }
+    }
+}
+
 impl Distribution<PieceColor> for Standard {
     fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> PieceColor {
         match rng.gen_range(1..PieceColor::ColorCount as usize) {
@@ -35,7 +51,7 @@ impl Distribution<PieceColor> for Standard {
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum HDirection {
     Left = -1,
     None = 0,
@@ -57,6 +73,12 @@ pub enum RotationAction {
     RotateCCW,
 }
 
+/* Bits marking which neighbors of a locked block belong to the same piece */
+pub const CONNECTED_UP: u8 = 1;
+pub const CONNECTED_RIGHT: u8 = 2;
+pub const CONNECTED_DOWN: u8 = 4;
+pub const CONNECTED_LEFT: u8 = 8;
+
 pub type PieceShape = [Vec<(i8, i8)>; 4];
 pub type KickData = [[Vec<(i8, i8)>; 3]; 4];
 
@@ -68,12 +90,15 @@ pub struct PieceType {
     pub spin_bonus: bool,
 }
 
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct Position {
     pub col: i32,
     pub row: i32,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Piece {
+    pub name: String, // Name from piece_data.toml
     pub position: Position,
     pub shape: PieceShape,
     pub color: PieceColor,
@@ -85,8 +110,9 @@ pub struct Piece {
 }
 
 impl Piece {
-    pub fn new(shape: PieceShape, color: PieceColor, kick_table: String, spin_bonus: bool) -> Self {
+    pub fn new(name: String, shape: PieceShape, color: PieceColor, kick_table: String, spin_bonus: bool) -> Self {
         Self {
+            name,
             position: Position {col: 0, row: 0},
             shape,
             color,
@@ -129,7 +155,8 @@ impl Piece {
         true
     }
 
-    pub fn rotate(&mut self, matrix: &Matrix, kick_data: &HashMap<String, KickData>, rotation: RotationAction) -> bool {
+    /* Returns None if the piece could not rotate, otherwise 0 for a plain rotation or the 1-based index of the wall kick used */
+    pub fn rotate(&mut self, matrix: &Matrix, kick_data: &HashMap<String, KickData>, rotation: RotationAction) -> Option<usize> {
         let target_rotation = (self.rotation + rotation as usize) % 4;
         if self.check_collision(matrix, 0, 0, target_rotation) {
             // Rotation causes a collision do wall kicks
@@ -138,25 +165,25 @@ impl Piece {
         self.rotation = target_rotation;
         self.update_ghost(matrix);
         self.last_move_was_rotation = true;
-        true
+        Some(0)
     }
 
-    fn wall_kick(&mut self, matrix: &Matrix, kick_data: &HashMap<String, KickData>, rotation: RotationAction) -> bool {
+    fn wall_kick(&mut self, matrix: &Matrix, kick_data: &HashMap<String, KickData>, rotation: RotationAction) -> Option<usize> {
         let target_rotation = (self.rotation + rotation as usize) % 4;
 
         let kick_movements = &kick_data.get(&self.kick_table).unwrap()[self.rotation][rotation as usize-1];
 
-        for (h, v) in kick_movements {
+        for (i, (h, v)) in kick_movements.iter().enumerate() {
             if !self.check_collision(matrix, *h as i32, *v as i32, target_rotation) {
                 self.rotation = target_rotation;
                 self.position.col += *h as i32;
                 self.position.row += *v as i32;
                 self.update_ghost(matrix);
                 self.last_move_was_rotation = true;
-                return true;
+                return Some(i + 1);
             }
         }
-        false
+        None
     }
 
     pub fn hard_drop(&mut self) {
@@ -167,11 +194,24 @@ impl Piece {
         &self.shape[self.rotation]
     }
 
-    pub fn lock(&self, matrix: &mut Matrix) {
-        for (rel_col, rel_row) in self.get_orientation().iter() {
+    pub fn rotation(&self) -> usize {
+        self.rotation
+    }
+
+    /* Matrix [col, row] of each block if the piece were hard dropped */
+    pub fn landing_cells(&self) -> Vec<(i32, i32)> {
+        self.get_orientation().iter()
+            .map(|(col, row)| (*col as i32 + self.position.col, *row as i32 + self.ghost_position))
+            .collect()
+    }
+
+    pub fn lock(&self, matrix: &mut Matrix, connections: &mut Connections) {
+        let blocks = self.get_orientation();
+        for (rel_col, rel_row) in blocks.iter() {
             let col = (*rel_col as i32 + self.position.col) as usize;
             let row = (*rel_row as i32 + self.position.row) as usize;
             matrix[row][col] = self.color;
+            connections[row][col] = neighbor_mask(blocks, *rel_col, *rel_row);
         }
     }
 
@@ -196,6 +236,10 @@ impl Piece {
         };
     }
 
+    pub fn overlaps(&self, matrix: &Matrix) -> bool {
+        self.check_collision(matrix, 0, 0, self.rotation)
+    }
+
     pub fn is_grounded(&self, matrix: &Matrix) -> bool {
         self.check_collision(matrix, 0, 1, self.rotation)
     }
@@ -248,6 +292,22 @@ pub fn shape_dimensions(shape: &[(i8, i8)]) -> (usize, usize) {
     (highest_x - lowest_x + 1, highest_y - lowest_y + 1)
 }
 
+fn neighbor_mask(blocks: &[(i8, i8)], col: i8, row: i8) -> u8 {
+    let neighbors = [
+        (CONNECTED_UP, (col, row-1)),
+        (CONNECTED_RIGHT, (col+1, row)),
+        (CONNECTED_DOWN, (col, row+1)),
+        (CONNECTED_LEFT, (col-1, row)),
+    ];
+    let mut mask = 0;
+    for (bit, neighbor) in neighbors.iter() {
+        if blocks.contains(neighbor) {
+            mask |= bit;
+        }
+    }
+    mask
+}
+
 /* Gets the tightest top left coordinate of the piece, used to ignore empty space in the bounding box while centering pieces */
 pub fn shape_top_left(shape: &[(i8, i8)]) -> (i32, i32) {
     let mut lowest_x = i32::MAX;
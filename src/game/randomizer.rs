@@ -1,11 +1,12 @@
 use super::piece::{Piece, PieceColor, PieceShape, PieceType, shape_dimensions, shape_top_left};
+use super::rng::GameRng;
 
 use std::collections::HashMap;
 
 use rand::Rng;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Clone, Copy, std::cmp::PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Copy, std::cmp::PartialEq)]
 pub enum RandomizerStyle {
     NBag, // A randomized list of all pieces once
     DoubleNBag, // A randomized list containing each piece twice
@@ -16,21 +17,35 @@ pub enum RandomizerStyle {
     Chaos, // Randomly generated pieces
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Randomizer {
     piece_list: Vec<String>,
     pub style: RandomizerStyle,
     remembered_piece: Option<String>,
+    rng: GameRng,
 }
 
 impl Randomizer {
-    pub fn new(piece_list: Vec<String>, style: RandomizerStyle) -> Self {
+    pub fn new(piece_list: Vec<String>, style: RandomizerStyle, seed: u64) -> Self {
         Self {
             piece_list,
             style,
             remembered_piece: None,
+            rng: GameRng::new(seed),
         }
     }
 
+    pub fn rng_state(&self) -> u64 {
+        self.rng.state()
+    }
+
+    /* Start over with a new piece list and style, the random numbers carry on from where they were */
+    pub fn reset(&mut self, piece_list: Vec<String>, style: RandomizerStyle) {
+        self.piece_list = piece_list;
+        self.style = style;
+        self.remembered_piece = None;
+    }
+
     pub fn generate_pieces(&mut self, cannot_start_with: &Option<Vec<String>>, piece_data: &HashMap<String, PieceType>) -> Vec<Piece> {
         let mut new_pieces: Vec<String>;
         match self.style {
@@ -48,22 +63,22 @@ impl Randomizer {
         create_pieces(new_pieces, piece_data)
     }
 
-    fn n_bag(&self) -> Vec<String> {
+    fn n_bag(&mut self) -> Vec<String> {
         let mut bag = self.piece_list.clone();
-        randomize(&mut bag);
+        randomize(&mut bag, &mut self.rng);
         bag
     }
 
-    fn double_n_bag(&self) -> Vec<String> {
+    fn double_n_bag(&mut self) -> Vec<String> {
         let mut bag = self.piece_list.clone();
         bag.append(&mut self.piece_list.clone());
-        randomize(&mut bag);
+        randomize(&mut bag, &mut self.rng);
         bag
     }
 
     fn minus_1_bag(&mut self) -> Vec<String> {
         let mut bag = self.piece_list.clone();
-        randomize(&mut bag);
+        randomize(&mut bag, &mut self.rng);
         if bag.len() < 2 {
             return bag;
         }
@@ -87,11 +102,10 @@ impl Randomizer {
         bag
     }
 
-    fn full_random(&self) -> Vec<String> {
+    fn full_random(&mut self) -> Vec<String> {
         let mut pieces = Vec::new();
-        let mut rng = rand::thread_rng();
         for _ in 0..10 {
-            let r = rng.gen_range(0..self.piece_list.len());
+            let r = self.rng.gen_range(0..self.piece_list.len());
             pieces.push(self.piece_list[r].clone());
         }
 
@@ -100,7 +114,7 @@ impl Randomizer {
 
     fn classic(&mut self) -> Vec<String> {
         let mut pieces = self.full_random();
-        let mut rng = rand::thread_rng();
+        let rng = &mut self.rng;
 
         // If the piece is the same as the previous piece reroll it once
         if let Some(prev) = &self.remembered_piece {
@@ -118,37 +132,35 @@ impl Randomizer {
     }
 
     fn streak(&mut self) -> Vec<String> {
-        let mut rng = rand::thread_rng();
-        let piece = self.piece_list[rng.gen_range(0..self.piece_list.len())].clone();
-        let count = rng.gen_range(2..6);
+        let piece = self.piece_list[self.rng.gen_range(0..self.piece_list.len())].clone();
+        let count = self.rng.gen_range(2..6);
         vec![piece; count]
     }
 
-    fn chaos(&self) -> Vec<Piece> {
+    fn chaos(&mut self) -> Vec<Piece> {
         let mut pieces = Vec::new();
         for _ in 0..10 {
-            let shape = generate_piece_shape();
-            let color: PieceColor = rand::random();
-            pieces.push(Piece::new(shape, color, "SRS".to_string(), false));
+            let shape = generate_piece_shape(&mut self.rng);
+            let color: PieceColor = self.rng.gen();
+            pieces.push(Piece::new(String::from("Chaos"), shape, color, "SRS".to_string(), false));
         }
         pieces
     }
 }
 
-fn randomize<T>(bag: &mut [T]) {
-    let mut rng = rand::thread_rng();
+fn randomize<T>(bag: &mut [T], rng: &mut GameRng) {
     let len = bag.len();
     for i in 0..len {
         bag.swap(i, rng.gen_range(i..len));
     }
 }
 
-fn create_pieces(piece_names: Vec<String>, piece_data: &HashMap<String, PieceType>) -> Vec<Piece> {
+pub fn create_pieces(piece_names: Vec<String>, piece_data: &HashMap<String, PieceType>) -> Vec<Piece> {
     let mut pieces = Vec::new();
     for name in piece_names {
         let piece = piece_data.get(&name)
             .unwrap_or_else(|| panic!("Tried to get {} from piece_data, but it was not found", name));
-        pieces.push(Piece::new(piece.shape.clone(), piece.color, piece.kick_table.clone(), piece.spin_bonus));
+        pieces.push(Piece::new(name.clone(), piece.shape.clone(), piece.color, piece.kick_table.clone(), piece.spin_bonus));
     }
 
     pieces
@@ -165,8 +177,7 @@ fn fix_starting_piece(list: &mut Vec<String>, disallowed: &[String]) {
     }
 }
 
-fn generate_piece_shape() -> PieceShape {
-    let mut rng = rand::thread_rng();
+fn generate_piece_shape(rng: &mut GameRng) -> PieceShape {
     let bound = rng.gen_range(3..=4);
 
     let mut initial_rotation = Vec::new();
@@ -1,59 +1,134 @@
-use super::{Game, Stats};
-use super::assets::GameAssets;
-use super::configuration::{GameMode, EndCondition, GameStat};
-use super::piece::{Piece, shape_dimensions, shape_top_left};
+use super::Game;
+use super::assets::{self, GameAssets};
+use super::configuration::GameStat;
+use super::layout::{Layout, PieceBox, StatList, Element};
+use super::effects::{self, Effects};
+use super::piece::{Piece, PieceColor, shape_dimensions, shape_top_left};
+use super::search::Placement;
 use crate::OFFSCREEN_ROWS;
+use crate::assets::GlyphAtlas;
+use crate::input::GameInput;
 
+use enum_map::EnumMap;
+use serde::Deserialize;
 use sdl2::{
     pixels::Color,
     rect::{Rect, Point},
-    render::{WindowCanvas, Texture},
+    render::{WindowCanvas, Texture, BlendMode},
 };
 
-const MATRIX_FRAME_WIDTH: usize = 160;
-const MATRIX_FRAME_HEIGHT: usize = 320;
+/* Layout of the optional overlay that shows which inputs are held */
+#[derive(Deserialize)]
+pub struct InputOverlay {
+    pub enabled: bool,
+    pub position: (i32, i32),
+    pub key_size: u32,
+    pub key_spacing: u32,
+    pub keys: Vec<KeyCap>,
+}
+
+/* A key in the input overlay, placed on a grid by column and row */
+#[derive(Deserialize)]
+pub struct KeyCap {
+    pub input: GameInput,
+    pub col: i32,
+    pub row: i32,
+}
 
-pub fn render(canvas: &mut WindowCanvas, game: &Game, assets: &mut GameAssets) -> Result<(), String> {
-    canvas.set_draw_color(Color::RGB(64, 64, 64));
+pub fn render(canvas: &mut WindowCanvas, game: &Game, effects: &Effects, assets: &mut GameAssets, layout: &Layout, overlay: &InputOverlay, inputs: &EnumMap<GameInput, bool>) -> Result<(), String> {
+    canvas.set_draw_color(assets.background_color);
     canvas.clear();
+    if let Some(background) = &assets.background {
+        canvas.copy(background, None, None)?;
+    }
 
-    // Scale the grid appropriately based on the size of the matrix
-    let grid_square_size = std::cmp::min(MATRIX_FRAME_HEIGHT / (game.matrix.len() - OFFSCREEN_ROWS), MATRIX_FRAME_WIDTH / game.matrix[0].len()) as u32;
+    let visible_rows = (game.matrix.len() - OFFSCREEN_ROWS) as u32;
+    let columns = game.matrix[0].len() as u32;
+    let (grid_square_size, matrix_offset) = matrix_grid(game, layout);
 
-    draw_gamemode_name(canvas, assets)?;
-    draw_matrix(canvas, &game.matrix, grid_square_size, assets)?;
-    draw_piece(canvas, &game.piece, grid_square_size, assets, game.ruleset.ghost_piece_enabled)?;
-    draw_preview(canvas, game, assets)?;
-    draw_held(canvas, game, assets)?;
-    draw_stats(canvas, game.level, &game.stats, &game.level_stats, &game.gamemode, &game.ruleset.level_up_condition, assets)?;
-    draw_frame(canvas, assets)?;
+    draw_gamemode_name(canvas, &layout.gamemode_name, assets)?;
+    draw_matrix(canvas, &game.matrix, &game.connections, grid_square_size, matrix_offset, assets)?;
+    if !game.is_clearing_lines() {
+        draw_piece(canvas, &game.piece, grid_square_size, matrix_offset, assets, game.ruleset.ghost_piece_enabled, &game.hint)?;
+    }
+    draw_effects(canvas, effects, columns, grid_square_size, matrix_offset, assets)?;
+    draw_garbage_meter(canvas, game, visible_rows, grid_square_size, matrix_offset)?;
+    draw_preview(canvas, game, &layout.preview, assets)?;
+    draw_held(canvas, game, &layout.hold, assets)?;
+    draw_stats(canvas, game, layout, assets)?;
+    if let Some(frame) = &layout.frame {
+        draw_frame(canvas, frame, assets)?;
+    }
+    if overlay.enabled {
+        draw_input_overlay(canvas, game, overlay, inputs, assets)?;
+    }
 
-    canvas.present();
     Ok(())
 }
 
-fn draw_gamemode_name(canvas: &mut WindowCanvas, assets: &mut GameAssets) -> Result<(), String> {
+/* Scale the grid appropriately based on the size of the matrix, returns the size of each square and the top left of the matrix */
+fn matrix_grid(game: &Game, layout: &Layout) -> (u32, Point) {
+    let (area_width, area_height) = layout.matrix.size;
+    let visible_rows = (game.matrix.len() - OFFSCREEN_ROWS) as u32;
+    let columns = game.matrix[0].len() as u32;
+    let grid_square_size = std::cmp::min(area_height / visible_rows, area_width / columns);
+    let matrix_offset = layout.matrix.anchor.align(layout.matrix.position, layout.matrix.size, columns * grid_square_size, visible_rows * grid_square_size);
+    (grid_square_size, matrix_offset)
+}
+
+/* Where a square of the matrix is drawn on screen, the row counts from the top of the visible rows */
+pub fn matrix_square(game: &Game, layout: &Layout, col: i32, row: i32) -> Rect {
+    let (grid_square_size, matrix_offset) = matrix_grid(game, layout);
+    let pos = get_grid_position(col, row, grid_square_size, matrix_offset);
+    Rect::new(pos.x, pos.y, grid_square_size, grid_square_size)
+}
+
+fn draw_gamemode_name(canvas: &mut WindowCanvas, element: &Element, assets: &mut GameAssets) -> Result<(), String> {
     let query = assets.gamemode_name_texture.query();
-    canvas.copy(&assets.gamemode_name_texture, None, Rect::new(5, 370-query.height as i32, query.width, query.height))?;
+    let point = element.anchor.place(element.position, query.width, query.height);
+    canvas.copy(&assets.gamemode_name_texture, None, Rect::new(point.x, point.y, query.width, query.height))?;
     Ok(())
 }
 
-fn draw_matrix(canvas: &mut WindowCanvas, matrix: &crate::game::Matrix, grid_square_size: u32, assets: &mut GameAssets) -> Result<(), String> {
-    let matrix_offset = Point::new(168, 16);
-
+fn draw_matrix(canvas: &mut WindowCanvas, matrix: &crate::game::Matrix, connections: &crate::game::Connections, grid_square_size: u32, matrix_offset: Point, assets: &mut GameAssets) -> Result<(), String> {
     assets.block_sheet.set_alpha_mod(255);
-    for (i, row) in matrix.iter().skip(OFFSCREEN_ROWS).enumerate() {
-        for (j, color) in row.iter().enumerate() {
+    for (i, (row, row_connections)) in matrix.iter().zip(connections.iter()).skip(OFFSCREEN_ROWS).enumerate() {
+        for (j, (color, connection)) in row.iter().zip(row_connections.iter()).enumerate() {
             let point = Point::new(j as i32, i as i32) * grid_square_size as i32 + matrix_offset;
-            canvas.copy(&assets.block_sheet, assets.block_sprites[*color as usize], Rect::new(point.x, point.y, grid_square_size, grid_square_size))?;
+            let sprite = match &assets.connected_block_sprites {
+                Some(connected) if *color != PieceColor::Empty => connected[*color as usize][*connection as usize],
+                _ => assets.block_sprites[*color as usize],
+            };
+            canvas.copy(&assets.block_sheet, sprite, Rect::new(point.x, point.y, grid_square_size, grid_square_size))?;
         }
     }
 
     Ok(())
 }
 
-fn draw_piece(canvas: &mut WindowCanvas, piece: &Piece, grid_square_size: u32, assets: &mut GameAssets, draw_ghost: bool) -> Result<(), String> {
-    let matrix_offset = Point::new(168, 16);
+fn draw_piece(canvas: &mut WindowCanvas, piece: &Piece, grid_square_size: u32, matrix_offset: Point, assets: &mut GameAssets, draw_ghost: bool, hint: &Option<Placement>) -> Result<(), String> {
+    // Draw the outline of the hinted placement, only on the edges that do not touch another of its blocks
+    if let Some(hint) = hint {
+        canvas.set_draw_color(assets.hint_color);
+        let size = grid_square_size;
+        for (col, row) in hint.cells.iter() {
+            if *row < OFFSCREEN_ROWS as i32 {
+                continue;
+            }
+            let pos = get_grid_position(*col, *row - OFFSCREEN_ROWS as i32, grid_square_size, matrix_offset);
+            let edges = [
+                ((0, -1), Rect::new(pos.x, pos.y, size, 2)),
+                ((0, 1), Rect::new(pos.x, pos.y + size as i32 - 2, size, 2)),
+                ((-1, 0), Rect::new(pos.x, pos.y, 2, size)),
+                ((1, 0), Rect::new(pos.x + size as i32 - 2, pos.y, 2, size)),
+            ];
+            for ((h, v), edge) in edges {
+                if !hint.cells.contains(&(col + h, row + v)) {
+                    canvas.fill_rect(edge)?;
+                }
+            }
+        }
+    }
 
     if draw_ghost {
         /*
@@ -62,7 +137,7 @@ fn draw_piece(canvas: &mut WindowCanvas, piece: &Piece, grid_square_size: u32, a
         */
 
         // Draw ghost piece outline
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
+        canvas.set_draw_color(assets.ghost_outline_color);
         assets.block_sheet.set_alpha_mod(255);
         for (col, row) in piece.get_orientation().iter() {
             if *row as i32 + piece.ghost_position >= OFFSCREEN_ROWS as i32 {
@@ -93,79 +168,170 @@ fn draw_piece(canvas: &mut WindowCanvas, piece: &Piece, grid_square_size: u32, a
     Ok(())
 }
 
-fn draw_preview(canvas: &mut WindowCanvas, game: &Game, assets: &mut GameAssets) -> Result<(), String> {
-    let preview_offset_x = 336;
-    let preview_offset_y = 16;
-    let preview_piece_box_size = 48;
-    let size = 10;
+fn draw_effects(canvas: &mut WindowCanvas, effects: &Effects, columns: u32, grid_square_size: u32, matrix_offset: Point, assets: &mut GameAssets) -> Result<(), String> {
+    // Cleared rows flash white and fade out
+    canvas.set_blend_mode(BlendMode::Blend);
+    for flash in effects.flashes.iter() {
+        if flash.row < OFFSCREEN_ROWS {
+            continue;
+        }
+        let alpha = (1.0 - effects::progress(flash.age, effects.flash_duration())) * 224.0;
+        canvas.set_draw_color(Color::RGBA(255, 255, 255, alpha as u8));
+        let pos = get_grid_position(0, (flash.row - OFFSCREEN_ROWS) as i32, grid_square_size, matrix_offset);
+        canvas.fill_rect(Rect::new(pos.x, pos.y, columns * grid_square_size, grid_square_size))?;
+    }
+    canvas.set_blend_mode(BlendMode::None);
+
+    // Particles are tiny blocks of the cleared colors
+    let particle_size = std::cmp::max(grid_square_size / 4, 2);
+    for particle in effects.particles.iter() {
+        let alpha = (1.0 - effects::progress(particle.age, effects.particle_duration())) * 255.0;
+        assets.block_sheet.set_alpha_mod(alpha as u8);
+        let x = particle.x * grid_square_size as f64 + matrix_offset.x as f64;
+        let y = (particle.y - OFFSCREEN_ROWS as f64) * grid_square_size as f64 + matrix_offset.y as f64;
+        canvas.copy(&assets.block_sheet, assets.block_sprites[particle.color as usize], Rect::new(x as i32, y as i32, particle_size, particle_size))?;
+    }
+    assets.block_sheet.set_alpha_mod(255);
+
+    // Popups stack upward from the highest cleared row and drift up as they fade
+    let matrix_center = matrix_offset.x + (columns * grid_square_size) as i32 / 2;
+    for (i, popup) in effects.popups.iter().enumerate() {
+        let progress = effects::progress(popup.age, effects.popup_duration());
+        let text = assets.popup_texture(&popup.text)?;
+        text.set_alpha_mod(((1.0 - progress) * 255.0) as u8);
+        let query = text.query();
+        let row = std::cmp::max(popup.row, OFFSCREEN_ROWS) - OFFSCREEN_ROWS;
+        let y = get_grid_position(0, row as i32, grid_square_size, matrix_offset).y - (query.height as usize * (i + 1)) as i32 - (progress * 16.0) as i32;
+        canvas.copy(text, None, Rect::new(matrix_center - query.width as i32 / 2, y, query.width, query.height))?;
+    }
+
+    Ok(())
+}
+
+/* Incoming garbage stacks up from the bottom along the left side of the matrix, brighter once it can enter */
+fn draw_garbage_meter(canvas: &mut WindowCanvas, game: &Game, visible_rows: u32, grid_square_size: u32, matrix_offset: Point) -> Result<(), String> {
+    let (ready, waiting) = game.incoming_garbage();
+    let meter_width = 4;
+    let x = matrix_offset.x - meter_width as i32 - 2;
+    let mut bottom = matrix_offset.y + (visible_rows * grid_square_size) as i32;
+    for (lines, color) in [(ready, Color::RGB(255, 48, 48)), (waiting, Color::RGB(255, 176, 48))] {
+        let height = std::cmp::min(lines, visible_rows) * grid_square_size;
+        if height == 0 {
+            continue;
+        }
+        bottom -= height as i32;
+        canvas.set_draw_color(color);
+        canvas.fill_rect(Rect::new(x, bottom, meter_width, height))?;
+    }
+    Ok(())
+}
+
+fn draw_preview(canvas: &mut WindowCanvas, game: &Game, preview: &PieceBox, assets: &mut GameAssets) -> Result<(), String> {
+    let (preview_offset_x, preview_offset_y) = preview.position;
 
     for (i, piece) in game.get_preview_pieces().iter().rev().enumerate() {
-        let offset_y = preview_offset_y + (preview_piece_box_size * i);
-        draw_centered_piece(canvas, &assets.block_sheet, &assets.block_sprites[piece.color as usize], &piece.shape[0], preview_offset_x, offset_y, preview_piece_box_size, size)?;
+        let offset_y = preview_offset_y + (preview.box_size * i);
+        draw_centered_piece(canvas, &assets.block_sheet, &assets.block_sprites[piece.color as usize], &piece.shape[0], preview_offset_x, offset_y, preview.box_size, preview.block_size)?;
     }
     Ok(())
 }
 
-fn draw_held(canvas: &mut WindowCanvas, game: &Game, assets: &mut GameAssets) -> Result<(), String> {
+fn draw_held(canvas: &mut WindowCanvas, game: &Game, hold: &PieceBox, assets: &mut GameAssets) -> Result<(), String> {
     if let Some(held) = &game.held {
-        let hold_offset_x = 112;
-        let hold_offset_y = 16;
-        let hold_box_size = 48;
-        let size = 10;
-
-        draw_centered_piece(canvas, &assets.block_sheet, &assets.block_sprites[held.color as usize], &held.shape[0], hold_offset_x, hold_offset_y, hold_box_size, size)?;
+        let (hold_offset_x, hold_offset_y) = hold.position;
+        draw_centered_piece(canvas, &assets.block_sheet, &assets.block_sprites[held.color as usize], &held.shape[0], hold_offset_x, hold_offset_y, hold.box_size, hold.block_size)?;
     }
     Ok(())
 }
 
-fn draw_stats(canvas: &mut WindowCanvas, level: usize, stats: &Stats, level_stats: &Stats, gamemode: &GameMode, level_up_cond: &EndCondition, assets: &mut GameAssets) -> Result<(), String> {
-    let texture_creator = canvas.texture_creator();
-    let mut stat_textures = assets.create_stat_textures(stats, level, &texture_creator)?;
-    let stats_offset_x = 440;
-    let stats_offset_y = 28;
-    let vertical_stat_spacing = 75;
+fn draw_stats(canvas: &mut WindowCanvas, game: &Game, layout: &Layout, assets: &mut GameAssets) -> Result<(), String> {
+    let stat_text = assets::stat_text(&game.stats, game.level);
+    let StatList { position: (stats_offset_x, stats_offset_y), spacing: vertical_stat_spacing } = layout.stats;
 
-    for(i, stat) in gamemode.displayed_stats.iter().enumerate() {
+    for(i, stat) in game.gamemode.displayed_stats.iter().enumerate() {
         let label = &assets.stat_labels[*stat];
-        let number = &mut stat_textures[*stat];
         let label_y = stats_offset_y + vertical_stat_spacing * i as i32;
-        draw_stat_and_label(canvas, label, number, stats_offset_x, label_y)?;
+        draw_stat_and_label(canvas, label, &stat_text[*stat], &mut assets.stat_glyphs, stats_offset_x, label_y)?;
     }
 
     // Draw level
     let label = &assets.stat_labels[GameStat::Level];
-    let number = &mut stat_textures[GameStat::Level];
-    draw_stat_and_label(canvas, label, number, 100, 80)?;
+    let (level_x, level_y) = layout.level;
+    draw_stat_and_label(canvas, label, &stat_text[GameStat::Level], &mut assets.stat_glyphs, level_x, level_y)?;
 
     // Next level label
-    let next_level = assets.create_next_level_label(level_up_cond, level_stats, &texture_creator)?;
-    let query = next_level.query();
-    canvas.copy(&next_level, None, Rect::new(100, 125, query.width, query.height))?;
+    let next_level = assets::next_level_text(&game.ruleset.level_up_condition, &game.level_stats);
+    let (width, height) = assets.next_level_glyphs.size_of(&next_level);
+    let point = layout.next_level.anchor.place(layout.next_level.position, width, height);
+    assets.next_level_glyphs.draw(canvas, &next_level, point.x, point.y)?;
 
     Ok(())
 }
 
-fn draw_stat_and_label(canvas: &mut WindowCanvas, label: &Texture, number: &mut Texture, stats_offset_x: i32, label_y: i32) -> Result<(), String> {
+fn draw_stat_and_label(canvas: &mut WindowCanvas, label: &Texture, number: &str, glyphs: &mut GlyphAtlas, stats_offset_x: i32, label_y: i32) -> Result<(), String> {
         let label_number_spacing = 18;
 
         let label_query = label.query();
         canvas.copy(label, None, Rect::new(stats_offset_x, label_y, label_query.width, label_query.height))?;
 
-        let query = number.query();
         let number_y = label_y + label_number_spacing;
-        number.set_color_mod(96, 96, 96);
-        canvas.copy(number, None, Rect::new(stats_offset_x+1, number_y+1, query.width, query.height))?;
-        number.set_color_mod(255, 255, 255);
-        canvas.copy(number, None, Rect::new(stats_offset_x, number_y, query.width, query.height))?;
+        glyphs.set_color_mod(Color::RGB(96, 96, 96));
+        glyphs.draw(canvas, number, stats_offset_x+1, number_y+1)?;
+        glyphs.set_color_mod(Color::RGB(255, 255, 255));
+        glyphs.draw(canvas, number, stats_offset_x, number_y)?;
 
         Ok(())
 }
 
-fn draw_frame(canvas: &mut WindowCanvas, assets: &GameAssets) -> Result<(), String> {
-    let frame_x = 104;
-    let frame_y = 8;
+fn draw_frame(canvas: &mut WindowCanvas, element: &Element, assets: &GameAssets) -> Result<(), String> {
     let query = assets.frame.query();
-    canvas.copy(&assets.frame, None, Rect::new(frame_x, frame_y, query.width, query.height))
+    let point = element.anchor.place(element.position, query.width, query.height);
+    canvas.copy(&assets.frame, None, Rect::new(point.x, point.y, query.width, query.height))
+}
+
+fn draw_input_overlay(canvas: &mut WindowCanvas, game: &Game, overlay: &InputOverlay, inputs: &EnumMap<GameInput, bool>, assets: &mut GameAssets) -> Result<(), String> {
+    let (offset_x, offset_y) = overlay.position;
+    let cell_size = (overlay.key_size + overlay.key_spacing) as i32;
+
+    // Draw key caps, lit while held
+    let mut bottom = offset_y;
+    for key in overlay.keys.iter() {
+        let x = offset_x + key.col * cell_size;
+        let y = offset_y + key.row * cell_size;
+        bottom = std::cmp::max(bottom, y + cell_size);
+
+        if inputs[key.input] {
+            canvas.set_draw_color(Color::RGB(224, 224, 224));
+        } else {
+            canvas.set_draw_color(Color::RGB(32, 32, 32));
+        }
+        canvas.fill_rect(Rect::new(x, y, overlay.key_size, overlay.key_size))?;
+
+        let label = &assets.input_labels[key.input];
+        let query = label.query();
+        let label_x = x + (overlay.key_size as i32 - query.width as i32) / 2;
+        let label_y = y + (overlay.key_size as i32 - query.height as i32) / 2;
+        canvas.copy(label, None, Rect::new(label_x, label_y, query.width, query.height))?;
+    }
+
+    // Draw DAS charge bar
+    let bar_width = std::cmp::max(cell_size * 4 - overlay.key_spacing as i32, 1) as u32;
+    let bar_height = 4;
+    canvas.set_draw_color(Color::RGB(32, 32, 32));
+    canvas.fill_rect(Rect::new(offset_x, bottom, bar_width, bar_height))?;
+    let charged_width = (bar_width as f64 * game.das_charge()) as u32;
+    if charged_width > 0 {
+        canvas.set_draw_color(Color::RGB(96, 192, 255));
+        canvas.fill_rect(Rect::new(offset_x, bottom, charged_width, bar_height))?;
+    }
+
+    // Draw input counts for the current piece and keys per piece
+    let text = format!("{} KEYS {:.2} KPP", game.piece_inputs, game.stats.keys_per_piece());
+    let counts = assets.input_count_texture(&text)?;
+    let query = counts.query();
+    canvas.copy(counts, None, Rect::new(offset_x, bottom + bar_height as i32 + 2, query.width, query.height))?;
+
+    Ok(())
 }
 
 fn get_grid_position(column: i32, row: i32, grid_square_size: u32, matrix_offset: Point) -> Point {
@@ -0,0 +1,62 @@
+/* Games in progress are saved when the program closes, and resumed from the gamemode's menu tile.
+   Piece and kick data are loaded again on resume, and the current handling settings are used */
+use super::{Config, Game, GameEvent, search::Heuristic, validate_data};
+
+use std::path::{Path, PathBuf};
+
+const SAVE_DIRECTORY: &str = "saves";
+
+pub fn save_path(gamemode_name: &str) -> PathBuf {
+    Path::new(SAVE_DIRECTORY).join(format!("{}.json", gamemode_name))
+}
+
+pub fn has_save(gamemode_name: &str) -> bool {
+    save_path(gamemode_name).is_file()
+}
+
+pub fn delete_save(gamemode_name: &str) -> Result<(), String> {
+    let path = save_path(gamemode_name);
+    if !path.is_file() {
+        return Ok(());
+    }
+    std::fs::remove_file(&path)
+        .map_err(|e| format!("Error deleting {}: {}", path.display(), e))
+}
+
+impl Game {
+    pub fn save(&self, gamemode_name: &str) -> Result<(), String> {
+        let path = save_path(gamemode_name);
+        std::fs::create_dir_all(SAVE_DIRECTORY)
+            .map_err(|e| format!("Error creating {}: {}", SAVE_DIRECTORY, e))?;
+        let data = serde_json::to_string(self)
+            .map_err(|e| format!("Error saving {}: {}", gamemode_name, e))?;
+        std::fs::write(&path, data)
+            .map_err(|e| format!("Error writing {}: {}", path.display(), e))
+    }
+
+    /* The save is deleted once it is loaded, so the same game can not be resumed twice */
+    pub fn resume(gamemode_name: &str) -> Result<Self, String> {
+        let path = save_path(gamemode_name);
+        let data = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Error opening {}: {}", path.display(), e))?;
+        let mut game: Game = serde_json::from_str(&data)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+
+        game.piece_data = crate::load_data(Path::new("data/piece_data.toml"))?;
+        game.kick_data = crate::load_data(Path::new("data/wall_kick_data.toml"))?;
+        validate_data(&game.piece_data, &game.kick_data, &game.ruleset.piece_list)?;
+        if game.gamemode.show_hint {
+            game.heuristic = Some(Heuristic::load()?);
+        }
+        game.apply_config(&Config::load()?);
+        game.update_hint();
+        game.record_spawn();
+        if let Some(track) = &game.music {
+            game.events.push(GameEvent::MusicChanged(track.to_string()));
+        }
+
+        std::fs::remove_file(&path)
+            .map_err(|e| format!("Error deleting {}: {}", path.display(), e))?;
+        Ok(game)
+    }
+}
@@ -0,0 +1,152 @@
+/* Move generation for the current piece, used for hints, bots, and anything else that needs to know where a piece can go */
+use super::Matrix;
+use super::piece::{HDirection, KickData, Piece, PieceColor, RotationAction, VDirection};
+use crate::input::GameInput;
+
+use std::{collections::{HashMap, HashSet, VecDeque}, path::Path};
+use serde::Deserialize;
+
+const MOVES: [GameInput; 8] = [
+    GameInput::Left,
+    GameInput::Right,
+    GameInput::ShiftLeft,
+    GameInput::ShiftRight,
+    GameInput::RotateCW,
+    GameInput::RotateCCW,
+    GameInput::Rotate180,
+    GameInput::InstantDrop,
+];
+
+/* A final resting place for a piece and the fewest inputs that reach it */
+#[derive(Clone)]
+pub struct Placement {
+    pub cells: Vec<(i32, i32)>, // Matrix [col, row] of each block, sorted so they can be compared
+    pub inputs: Vec<GameInput>, // Ends with a hard drop, starts with a hold if the held piece is placed
+    pub score: f64,
+}
+
+/* Weights for each feature of the matrix after a placement. Negative weights are penalties */
+#[derive(Deserialize)]
+pub struct Heuristic {
+    pub height: f64, // Sum of the heights of every column
+    pub lines: f64, // Lines cleared by the placement
+    pub holes: f64, // Empty cells with a block somewhere above them
+    pub bumpiness: f64, // Sum of the height differences between neighboring columns
+}
+
+impl Heuristic {
+    pub fn load() -> Result<Self, String> {
+        crate::load_data_ron(Path::new("config/heuristic.ron"))
+    }
+
+    pub fn evaluate(&self, matrix: &Matrix, cells: &[(i32, i32)]) -> f64 {
+        let mut filled: Vec<Vec<bool>> = matrix.iter()
+            .map(|row| row.iter().map(|x| *x != PieceColor::Empty).collect())
+            .collect();
+        for (col, row) in cells {
+            filled[*row as usize][*col as usize] = true;
+        }
+        let rows = filled.len();
+        filled.retain(|row| !row.iter().all(|x| *x));
+        let lines = rows - filled.len();
+
+        // Rows removed from the top are empty, so heights can be measured from the remaining rows
+        let heights: Vec<usize> = (0..matrix[0].len())
+            .map(|col| match filled.iter().position(|row| row[col]) {
+                Some(top) => filled.len() - top,
+                None => 0,
+            })
+            .collect();
+        let holes = (0..matrix[0].len())
+            .map(|col| filled.iter().skip(filled.len() - heights[col]).filter(|row| !row[col]).count())
+            .sum::<usize>();
+        let bumpiness = heights.windows(2)
+            .map(|pair| (pair[0] as i32 - pair[1] as i32).abs())
+            .sum::<i32>();
+
+        self.height * heights.iter().sum::<usize>() as f64
+            + self.lines * lines as f64
+            + self.holes * holes as f64
+            + self.bumpiness * bumpiness as f64
+    }
+}
+
+/* Every placement the piece can reach, found by searching every input from where the piece is now.
+   Soft drops followed by shifts and rotations reach tucks and spins. Each input is a separate tap,
+   so DAS never charges while the inputs are played. Placements are unscored and in order of fewest inputs */
+pub fn placements(piece: &Piece, matrix: &Matrix, kick_data: &HashMap<String, KickData>, instant_gravity: bool) -> Vec<Placement> {
+    let mut start = piece.clone();
+    if instant_gravity {
+        start.hard_drop();
+    }
+    let mut seen = HashSet::new();
+    seen.insert(state_key(&start));
+    let mut queue = VecDeque::new();
+    queue.push_back((start, Vec::new()));
+    let mut found = HashSet::new();
+    let mut placements = Vec::new();
+
+    while let Some((piece, path)) = queue.pop_front() {
+        let mut cells = piece.landing_cells();
+        cells.sort_unstable();
+        if found.insert(cells.clone()) {
+            let mut inputs = path.clone();
+            inputs.push(GameInput::HardDrop);
+            placements.push(Placement { cells, inputs, score: 0.0 });
+        }
+
+        for input in MOVES {
+            let mut next = piece.clone();
+            if !apply_input(&mut next, matrix, kick_data, input) {
+                continue;
+            }
+            if instant_gravity {
+                next.hard_drop();
+            }
+            if seen.insert(state_key(&next)) {
+                let mut next_path = path.clone();
+                next_path.push(input);
+                queue.push_back((next, next_path));
+            }
+        }
+    }
+    placements
+}
+
+/* Search for the fewest inputs that hard drop the piece into the target cells, which must be sorted */
+pub fn find_path(piece: &Piece, matrix: &Matrix, kick_data: &HashMap<String, KickData>, instant_gravity: bool, target: &[(i32, i32)]) -> Option<Vec<GameInput>> {
+    placements(piece, matrix, kick_data, instant_gravity).into_iter()
+        .find(|x| x.cells == target)
+        .map(|x| x.inputs)
+}
+
+fn state_key(piece: &Piece) -> (i32, i32, usize) {
+    (piece.position.col, piece.position.row, piece.rotation())
+}
+
+/* Returns false if the input would not change the piece */
+fn apply_input(piece: &mut Piece, matrix: &Matrix, kick_data: &HashMap<String, KickData>, input: GameInput) -> bool {
+    match input {
+        GameInput::Left => piece.movement(matrix, HDirection::Left, VDirection::None),
+        GameInput::Right => piece.movement(matrix, HDirection::Right, VDirection::None),
+        GameInput::ShiftLeft => shift(piece, matrix, HDirection::Left),
+        GameInput::ShiftRight => shift(piece, matrix, HDirection::Right),
+        GameInput::RotateCW => piece.rotate(matrix, kick_data, RotationAction::RotateCW).is_some(),
+        GameInput::RotateCCW => piece.rotate(matrix, kick_data, RotationAction::RotateCCW).is_some(),
+        GameInput::Rotate180 => piece.rotate(matrix, kick_data, RotationAction::Rotate180).is_some(),
+        GameInput::InstantDrop => {
+            let moved = piece.position.row != piece.ghost_position;
+            piece.hard_drop();
+            moved
+        }
+        _ => false,
+    }
+}
+
+fn shift(piece: &mut Piece, matrix: &Matrix, direction: HDirection) -> bool {
+    let mut moved = false;
+    while piece.movement(matrix, direction, VDirection::None) {
+        moved = true;
+    }
+    moved
+}
@@ -0,0 +1,184 @@
+/* Custom starting positions made in the editor, saved as RON files that can be shared */
+use super::{Config, Game, History, Matrix, piece::{Piece, PieceColor}, randomizer::create_pieces};
+
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+/* Letters for each color in saved rows, named after the standard piece of that color. G is garbage */
+const COLOR_LETTERS: [(char, PieceColor); 9] = [
+    ('.', PieceColor::Empty),
+    ('I', PieceColor::Cyan),
+    ('T', PieceColor::Magenta),
+    ('O', PieceColor::Yellow),
+    ('J', PieceColor::Blue),
+    ('L', PieceColor::Orange),
+    ('S', PieceColor::Green),
+    ('Z', PieceColor::Red),
+    ('G', PieceColor::Gray),
+];
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Setup {
+    pub gamemode: String,
+    pub rows: Vec<String>, // From the top down, the last row is the bottom of the matrix
+    pub queue: Vec<String>, // Starts with the piece in the matrix, the randomizer carries on after the last one
+    pub hold: Option<String>,
+}
+
+impl Setup {
+    pub fn new(gamemode: String) -> Self {
+        Self {
+            gamemode,
+            rows: Vec::new(),
+            queue: Vec::new(),
+            hold: None,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        crate::load_data_ron(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let data = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| format!("Error saving {}: {}", path.display(), e))?;
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)
+                .map_err(|e| format!("Error creating {}: {}", directory.display(), e))?;
+        }
+        std::fs::write(path, data)
+            .map_err(|e| format!("Error writing {}: {}", path.display(), e))
+    }
+
+    /* Store the blocks of a matrix, leaving out the empty rows at the top */
+    pub fn set_rows(&mut self, matrix: &Matrix) {
+        let first_used = matrix.iter().position(|row| row.iter().any(|x| *x != PieceColor::Empty)).unwrap_or(matrix.len());
+        self.rows = matrix[first_used..].iter()
+            .map(|row| row.iter().map(|color| color_letter(*color)).collect())
+            .collect();
+    }
+
+    /* Copy the rows into the bottom of a matrix, which must be at least as large */
+    pub fn fill_matrix(&self, matrix: &mut Matrix) -> Result<(), String> {
+        if self.rows.len() > matrix.len() {
+            return Err(format!("Setup has {} rows, but the matrix only has {}", self.rows.len(), matrix.len()));
+        }
+        let offset = matrix.len() - self.rows.len();
+        for (i, text) in self.rows.iter().enumerate() {
+            let row = &mut matrix[offset + i];
+            if text.chars().count() > row.len() {
+                return Err(format!("Setup row {} is wider than the matrix", text));
+            }
+            row.fill(PieceColor::Empty);
+            for (block, letter) in row.iter_mut().zip(text.chars()) {
+                *block = letter_color(letter).ok_or_else(|| format!("Setup row {} has unknown block {}", text, letter))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn color_letter(color: PieceColor) -> char {
+    COLOR_LETTERS.iter().find(|(_, x)| *x == color).map_or('.', |(letter, _)| *letter)
+}
+
+fn letter_color(letter: char) -> Option<PieceColor> {
+    COLOR_LETTERS.iter().find(|(x, _)| *x == letter).map(|(_, color)| *color)
+}
+
+impl Game {
+    /* A new game of the setup's gamemode that starts from the setup's matrix, queue and hold */
+    pub fn from_setup(setup: &Setup, seed: u64) -> Result<Self, String> {
+        let mut game = Game::with_settings(&setup.gamemode, seed, Config::load()?)?;
+        if let Some(name) = setup.queue.iter().chain(setup.hold.iter()).find(|x| !game.piece_data.contains_key(*x)) {
+            return Err(format!("Setup uses piece {}, which is not in piece_data", name));
+        }
+
+        setup.fill_matrix(&mut game.matrix)?;
+        for row in game.connections.iter_mut() {
+            row.fill(0);
+        }
+        game.add_setup_pieces(setup);
+        game.setup_changed();
+        Ok(game)
+    }
+
+    /* Change one block of the matrix, for the editor. The hint is left alone since searching after every block is too slow */
+    pub fn set_block(&mut self, col: usize, row: usize, color: PieceColor) {
+        self.matrix[row][col] = color;
+        self.connections[row][col] = 0;
+        self.piece.update_ghost(&self.matrix);
+    }
+
+    /* Swap the queue and hold of a game made from the previous setup for the new setup's, for the editor.
+       The pieces the randomizer made stay the same */
+    pub fn change_setup_pieces(&mut self, previous: &Setup, setup: &Setup) {
+        if !previous.queue.is_empty() {
+            let generated = self.piece_queue.len() - (previous.queue.len() - 1);
+            self.piece_queue.truncate(generated);
+            self.piece = self.piece_queue.pop().unwrap();
+        }
+        self.add_setup_pieces(setup);
+        self.piece.reset_position(&self.matrix);
+        self.setup_changed();
+    }
+
+    /* The piece the randomizer made to start with goes back in the queue, after the setup's pieces */
+    fn add_setup_pieces(&mut self, setup: &Setup) {
+        self.held = setup.hold.as_ref().map(|name| setup_piece(name, self));
+        if let Some((first, rest)) = setup.queue.split_first() {
+            // The end of the queue is played first
+            let first = setup_piece(first, self);
+            let generated = std::mem::replace(&mut self.piece, first);
+            self.piece_queue.push(generated);
+            self.piece_queue.extend(create_pieces(rest.iter().rev().cloned().collect(), &self.piece_data));
+        }
+    }
+
+    fn setup_changed(&mut self) {
+        self.piece.update_ghost(&self.matrix);
+        self.spawned_piece = self.piece.clone();
+        self.update_hint();
+        self.history = History::default();
+        self.record_spawn();
+    }
+
+    pub fn piece_list(&self) -> &[String] {
+        &self.ruleset.piece_list
+    }
+
+    pub fn piece_color(&self, name: &str) -> Option<PieceColor> {
+        self.piece_data.get(name).map(|x| x.color)
+    }
+}
+
+fn setup_piece(name: &str, game: &Game) -> Piece {
+    let mut piece = create_pieces(vec![name.to_string()], &game.piece_data).remove(0);
+    piece.reset_position(&game.matrix);
+    piece
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pieces(game: &Game) -> Vec<String> {
+        std::iter::once(&game.piece).chain(game.piece_queue.iter().rev()).chain(game.held.iter())
+            .map(|x| x.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn changing_pieces_matches_a_new_game() {
+        let mut first = Setup::new(String::from("practice"));
+        first.queue = ["T", "I", "O"].map(String::from).to_vec();
+        let mut second = Setup::new(String::from("practice"));
+        second.hold = Some(String::from("S"));
+
+        for (from, to) in [(&first, &second), (&second, &first)] {
+            let mut game = Game::from_setup(from, 5).unwrap();
+            game.change_setup_pieces(from, to);
+            assert_eq!(pieces(&game), pieces(&Game::from_setup(to, 5).unwrap()));
+        }
+    }
+}
@@ -18,6 +18,9 @@ pub enum GameInput {
     RotateCCW,
     Hold,
     Reset,
+    Pause,
+    Undo, // Only in practice rulesets
+    Redo,
 }
 
 #[derive(Serialize, Deserialize, enum_map::Enum, Clone, Copy, PartialEq, Eq)]
@@ -30,47 +33,116 @@ pub enum MenuInput {
     Cancel,
 }
 
+#[derive(Serialize, Deserialize, enum_map::Enum, Clone, Copy, PartialEq, Eq)]
+pub enum EditorInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Paint, // Fill squares with the brush while held
+    Erase,
+    NextBrush,
+    PreviousBrush,
+    AddToQueue, // Add the brush's piece to the end of the queue
+    RemoveFromQueue,
+    Hold, // Hold the brush's piece, or nothing if it is already held
+    Save,
+    Load, // Load the next saved position
+    Play,
+    Exit,
+}
+
 pub fn handle_input_event<T>(input: &mut EnumMap<T, bool>, event: Event, bindings: &HashMap<String, T>)
 where T: enum_map::Enum<bool> + Copy {
-    let (button, state) = match event {
-        Event::KeyDown { keycode: Some(key), ..} => {
-            (format!("Key({})", key.to_string()), true)
+    if let Some((x, state, _)) = read_binding(&event, bindings) {
+        input[x] = state;
+    };
+}
+
+/* An input event along with the time it occurred, in µs since the start of the frame */
+pub struct TimedEvent {
+    pub event: Event,
+    pub time: u128,
+}
+
+impl TimedEvent {
+    pub fn new(event: Event, frame_end: u32, elapsed: u128) -> Self {
+        // SDL timestamps are in milliseconds, count backward from when the events were polled
+        let age = frame_end.saturating_sub(event.get_timestamp()) as u128 * 1000;
+        Self {
+            time: elapsed.saturating_sub(age),
+            event,
         }
-        Event::ControllerButtonDown { button: btn, ..} => {
-            (format!("Btn({})", btn.string()), true)
+    }
+}
+
+/* Remove the events that happen before the given time, the remaining events are rebased to start at that time */
+pub fn take_events_before(events: &mut Vec<TimedEvent>, time: u128) -> Vec<TimedEvent> {
+    let split = events.iter().position(|e| e.time >= time).unwrap_or(events.len());
+    let taken = events.drain(..split).collect();
+    for event in events.iter_mut() {
+        event.time -= time;
+    }
+    taken
+}
+
+/* A press or release of a bound input, timestamp is the SDL timestamp (ms) and time is µs since the start of the frame */
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct InputChange<T> {
+    pub input: T,
+    pub pressed: bool,
+    pub timestamp: u32,
+    pub time: u128,
+}
+
+pub fn read_timed_input_event<T>(timed_event: &TimedEvent, bindings: &HashMap<String, T>) -> Option<InputChange<T>>
+where T: Copy {
+    read_binding(&timed_event.event, bindings).map(|(input, pressed, timestamp)| InputChange {
+        input,
+        pressed,
+        timestamp,
+        time: timed_event.time,
+    })
+}
+
+/* Apply an input change, recording the time of each press so the order of held inputs is known */
+pub fn apply_input_change<T>(input: &mut EnumMap<T, bool>, press_times: &mut EnumMap<T, u32>, change: &InputChange<T>)
+where T: enum_map::Enum<bool> + enum_map::Enum<u32> + Copy {
+    if change.pressed && !input[change.input] {
+        press_times[change.input] = change.timestamp;
+    }
+    input[change.input] = change.pressed;
+}
+
+fn read_binding<T>(event: &Event, bindings: &HashMap<String, T>) -> Option<(T, bool, u32)>
+where T: Copy {
+    let (button, state, timestamp) = match event {
+        Event::KeyDown { keycode: Some(key), timestamp, ..} => {
+            (format!("Key({})", key.to_string()), true, *timestamp)
+        }
+        Event::ControllerButtonDown { button: btn, timestamp, ..} => {
+            (format!("Btn({})", btn.string()), true, *timestamp)
         }
-        Event::KeyUp { keycode: Some(key), ..} => {
-            (format!("Key({})", key.to_string()), false)
+        Event::KeyUp { keycode: Some(key), timestamp, ..} => {
+            (format!("Key({})", key.to_string()), false, *timestamp)
         }
-        Event::ControllerButtonUp { button: btn, ..} => {
-            (format!("Btn({})", btn.string()), false)
+        Event::ControllerButtonUp { button: btn, timestamp, ..} => {
+            (format!("Btn({})", btn.string()), false, *timestamp)
         }
-        _ => { return }
+        _ => { return None }
     };
 
-    if let Some(x) = bindings.get(&button) {
-        input[*x] = state;
-    };
+    bindings.get(&button).map(|x| (*x, state, timestamp))
 }
 
-pub fn open_game_controller(game_controller_subsystem: GameControllerSubsystem) -> Result<Option<GameController>, String> {
+/* Open every connected game controller, versus needs one for each player */
+pub fn open_game_controllers(game_controller_subsystem: GameControllerSubsystem) -> Result<Vec<GameController>, String> {
     let available = game_controller_subsystem
         .num_joysticks()
         .map_err(|e| format!("can't enumerate joysticks: {}", e))?;
 
-    // Iterate over all available joysticks and look for game controllers.
-    Ok((0..available).find_map(|id| {
-        if !game_controller_subsystem.is_game_controller(id) {
-            return None;
-        }
-
-        match game_controller_subsystem.open(id) {
-            Ok(c) => {
-                // We managed to find and open a game controller,
-                // exit the loop
-                Some(c)
-            }
-            Err(_) => None,
-        }
-    }))
+    Ok((0..available)
+        .filter(|id| game_controller_subsystem.is_game_controller(*id))
+        .filter_map(|id| game_controller_subsystem.open(id).ok())
+        .collect())
 }
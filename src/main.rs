@@ -3,6 +3,11 @@ mod input;
 mod assets;
 mod menu;
 mod scenes;
+mod theme;
+mod display;
+mod audio;
+mod bot;
+mod net;
 
 use std::time::Instant;
 
@@ -11,61 +16,125 @@ use sdl2::{
     image::InitFlag,
     keyboard::Scancode,
 };
-use serde::{de::DeserializeOwned};
+use serde::{Deserialize, de::DeserializeOwned};
 
 const OFFSCREEN_ROWS: usize = 5;
+const MAX_FRAME_TIME: u128 = 250_000; // Limit how far the simulation can fall behind after a stall, in µs
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default = "default_tick_rate")]
+    tick_rate: u32, // Simulation updates per second
+    #[serde(default = "default_theme")]
+    theme: String,
+    #[serde(default)]
+    display: display::DisplayConfig,
+}
+
+fn default_tick_rate() -> u32 {
+    1000
+}
+
+fn default_theme() -> String {
+    String::from("default")
+}
 
 fn main() -> Result<(), String> {
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
     let _image_context = sdl2::image::init(InitFlag::PNG)?;
     let game_controller_subsystem = sdl_context.game_controller()?;
-    let _controller = input::open_game_controller(game_controller_subsystem)?;
+    let _controllers = input::open_game_controllers(game_controller_subsystem)?;
     let ttf_context = sdl2::ttf::init()
         .map_err(|e| e.to_string())?;
 
+    let mut audio = audio::Audio::new(&sdl_context)?;
+    let config: Config = load_data(std::path::Path::new("config/config.toml"))?;
     let window = video_subsystem
-        .window("idk", 1280, 720)
+        .window("idk", config.display.width, config.display.height)
         .build()
         .map_err(|e| e.to_string())?;
 
-    let mut canvas = window.into_canvas()
-        .present_vsync()
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    canvas.set_logical_size(640, 360)
-        .map_err(|e| e.to_string())?;
+    let mut scene_manager = scenes::SceneManager::new(scenes::Scene::MainMenu(scenes::menu_scene::MenuScene::new()?));
 
-    let texture_creator = canvas.texture_creator();
-    let mut assets = assets::Assets::new(&texture_creator, &ttf_context)?;
+    // Scenes get to save their state however the loop ends, including with an error
+    let result = run(&mut scene_manager, &sdl_context, &ttf_context, &mut audio, config, window);
+    scene_manager.quit();
+    result
+}
 
-    let mut scene_manager = scenes::SceneManager::new(scenes::Scene::MainMenu(scenes::menu_scene::MenuScene::new()?));
+fn run(scene_manager: &mut scenes::SceneManager, sdl_context: &sdl2::Sdl, ttf_context: &sdl2::ttf::Sdl2TtfContext, audio: &mut audio::Audio, mut config: Config, mut window: sdl2::video::Window) -> Result<(), String> {
+    let tick_length = 1_000_000 / config.tick_rate.max(1) as u128;
 
+    let timer_subsystem = sdl_context.timer()?;
     let mut current_time = Instant::now();
+    let mut accumulator = 0;
+    let mut pending_events = Vec::new();
     let mut event_pump = sdl_context.event_pump()?;
-    'running: loop {
-        let elapsed = current_time.elapsed().as_micros();
-        current_time = Instant::now();
-
-        let mut input_events = Vec::new();
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit {..}
-                | Event::KeyDown { scancode: Some(Scancode::Escape), .. } => {
-                    break 'running;
-                }
-                Event::KeyDown{ repeat: false, ..} | Event::KeyUp{ repeat: false, ..}
-                | Event::ControllerButtonDown{..} | Event::ControllerButtonUp{..} => {
-                    input_events.push(event);
+    /* The canvas and everything drawn with it are rebuilt when a setting that needs a new renderer changes */
+    loop {
+        let mut canvas = display::create_canvas(window, &config.display)?;
+        let texture_creator = canvas.texture_creator();
+        let mut assets = assets::Assets::new(&texture_creator, ttf_context, theme::Theme::load(&config.theme)?)?;
+        let mut fullscreen_key = config.display.fullscreen_scancode()?;
+        let mut reload_key = config.display.reload_scancode()?;
+
+        let rebuild = 'running: loop {
+            let elapsed = std::cmp::min(current_time.elapsed().as_micros(), MAX_FRAME_TIME);
+            current_time = Instant::now();
+
+            let frame_end = timer_subsystem.ticks();
+            for event in event_pump.poll_iter() {
+                match event {
+                    Event::Quit {..}
+                    | Event::KeyDown { scancode: Some(Scancode::Escape), .. } => {
+                        break 'running false;
+                    }
+                    Event::KeyDown { scancode: Some(scancode), repeat: false, .. } if scancode == fullscreen_key => {
+                        display::toggle_fullscreen(&mut canvas, &config.display)?;
+                    }
+                    Event::KeyDown { scancode: Some(scancode), repeat: false, .. } if scancode == reload_key => {
+                        let new_config: Config = load_data(std::path::Path::new("config/config.toml"))?;
+                        let needs_rebuild = new_config.display.vsync != config.display.vsync || new_config.theme != config.theme;
+                        display::apply(&mut canvas, &new_config.display)?;
+                        fullscreen_key = new_config.display.fullscreen_scancode()?;
+                        reload_key = new_config.display.reload_scancode()?;
+                        config = new_config;
+                        audio.reload_config()?;
+                        if needs_rebuild {
+                            break 'running true;
+                        }
+                    }
+                    Event::KeyDown{ repeat: false, ..} | Event::KeyUp{ repeat: false, ..}
+                    | Event::ControllerButtonDown{..} | Event::ControllerButtonUp{..} => {
+                        // Events are timed from the last simulated point, which lags behind by the accumulator
+                        pending_events.push(input::TimedEvent::new(event, frame_end, accumulator + elapsed));
+                    }
+                    _ => {},
                 }
-                _ => {},
             }
-        }
 
-        scene_manager.update(&mut canvas, &mut assets, input_events, elapsed)?;
-    }
+            /* Run the simulation in fixed steps so that it does not depend on the frame rate */
+            accumulator += elapsed;
+            while accumulator >= tick_length {
+                let input_events = input::take_events_before(&mut pending_events, tick_length);
+                scene_manager.update(input_events, tick_length, audio);
+                audio.update(tick_length);
+                accumulator -= tick_length;
+            }
+
+            scene_manager.render(&mut canvas, &mut assets)?;
+            display::limit_frame_rate(current_time, &config.display);
+        };
 
+        // Textures have to be destroyed before the renderer that created them
+        drop(assets);
+        drop(texture_creator);
+        if !rebuild {
+            break;
+        }
+        window = canvas.into_window();
+    }
     Ok(())
 }
 
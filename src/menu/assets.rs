@@ -1,22 +1,26 @@
 use std::{path::Path, hash::{Hash, Hasher}, collections::hash_map::DefaultHasher};
 
-use sdl2::{image::LoadTexture, render::{Texture, TextureCreator}, video::WindowContext, ttf::Sdl2TtfContext, pixels::Color};
+use sdl2::{render::{Texture, TextureCreator}, video::WindowContext, ttf::Sdl2TtfContext, pixels::Color};
 use crate::assets::create_text_texture;
+use crate::theme::Theme;
 
 pub struct MenuAssets<'a> {
     pub menu_bg: Texture<'a>,
     pub menu_tile_overlay: Texture<'a>,
     pub tile_labels: Vec<Texture<'a>>,
     pub tile_colors: Vec<Color>,
+    pub resume_label: Texture<'a>, // Shown on tiles of gamemodes with a saved game
+    pub new_label: Texture<'a>, // Shown under RESUME while choosing between them
+    pub background_color: Color,
 }
 
 impl<'a> MenuAssets<'a> {
-    pub fn new(texture_creator: &'a TextureCreator<WindowContext>, ttf_context: &'a Sdl2TtfContext) -> Result<Self, String> {
-        let menu_bg = texture_creator.load_texture("assets/menu_bg.png")?;
-        let menu_tile_overlay = texture_creator.load_texture("assets/menu_tile_overlay.png")?;
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>, ttf_context: &'a Sdl2TtfContext, theme: &Theme) -> Result<Self, String> {
+        let menu_bg = theme.load_texture(texture_creator, &theme.menu_background)?;
+        let menu_tile_overlay = theme.load_texture(texture_creator, &theme.menu_tile_overlay)?;
 
-        let font = ttf_context.load_font(Path::new("assets/Hack-Bold.ttf"), 28)?;
-        let label_color = Color::RGB(255, 255, 255);
+        let font = ttf_context.load_font(Path::new(&theme.fonts.menu), 28)?;
+        let label_color = Color::from(theme.colors.menu_label);
         let mut tile_labels = Vec::new();
         let mut tile_colors = Vec::new();
         let label_text: Vec<String> = crate::load_data_ron(Path::new(&"config/menu_config.ron"))?;
@@ -24,14 +28,26 @@ impl<'a> MenuAssets<'a> {
             tile_labels.push(create_text_texture(&text.to_uppercase(), label_color, &font, texture_creator)?);
             tile_colors.push(generate_color(text));
         }
+        tile_labels.push(create_text_texture("VERSUS", label_color, &font, texture_creator)?);
+        tile_colors.push(generate_color("VERSUS"));
+        for label in ["HOST ONLINE", "JOIN ONLINE", "EDITOR"] {
+            tile_labels.push(create_text_texture(label, label_color, &font, texture_creator)?);
+            tile_colors.push(generate_color(label));
+        }
         tile_labels.push(create_text_texture("SETTINGS", label_color, &font, texture_creator)?);
         tile_colors.push(Color::RGB(128, 128, 128));
+        let small_font = ttf_context.load_font(Path::new(&theme.fonts.menu), 16)?;
+        let resume_label = create_text_texture("RESUME", label_color, &small_font, texture_creator)?;
+        let new_label = create_text_texture("NEW", label_color, &small_font, texture_creator)?;
 
         Ok(Self {
             menu_bg,
             menu_tile_overlay,
             tile_labels,
             tile_colors,
+            resume_label,
+            new_label,
+            background_color: Color::from(theme.colors.menu_background),
         })
     }
 }
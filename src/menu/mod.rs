@@ -4,6 +4,7 @@ use std::path::Path;
 use std::cmp::{min, max};
 
 use crate::input::MenuInput;
+use crate::net::Role;
 
 use enum_map::EnumMap;
 
@@ -12,19 +13,27 @@ const ARR_THRESHOLD: u128 = 150_000;
 
 enum MenuTile {
     Gamemode(String),
+    Versus,
+    Online(Role),
+    Editor,
     Settings,
 }
 
 pub enum MenuStatus {
     Exit,
     Continue,
-    Game(String),
+    Game { name: String, resume: bool },
+    Versus,
+    Online(Role),
+    Editor,
     Settings,
 } 
 
 pub struct Menu {
     selected_index: usize,
     tiles: Vec<MenuTile>,
+    saved: Vec<bool>, // Tiles of gamemodes with a saved game to resume
+    choice: Option<bool>, // Whether resume or a new game is picked, while choosing between them on a saved tile
 
     prev_movement_input: MenuInput,
     arr_timer: u128,
@@ -35,27 +44,62 @@ impl Menu {
     pub fn new() -> Result<Self, String> {
         let gamemode_names: Vec<String> = crate::load_data_ron(Path::new(&"config/menu_config.ron"))?;
         let mut tiles: Vec<MenuTile> = gamemode_names.iter().map(|name| MenuTile::Gamemode(name.to_string())).collect();
+        tiles.push(MenuTile::Versus);
+        tiles.push(MenuTile::Online(Role::Host));
+        tiles.push(MenuTile::Online(Role::Join));
+        tiles.push(MenuTile::Editor);
         tiles.push(MenuTile::Settings);
-        Ok(Self {
+        let mut menu = Self {
             selected_index: 0,
+            saved: vec![false; tiles.len()],
+            choice: None,
             tiles,
 
             prev_movement_input: MenuInput::Cancel,
             arr_timer: 0,
             das_timer: 0,
-        })
+        };
+        menu.refresh_saves();
+        Ok(menu)
+    }
+
+    /* Check which gamemodes have a saved game, saves are written when the program closes and removed when resumed */
+    pub fn refresh_saves(&mut self) {
+        self.saved = self.tiles.iter()
+            .map(|tile| matches!(tile, MenuTile::Gamemode(name) if crate::game::save::has_save(name)))
+            .collect();
+    }
+
+    pub fn is_saved(&self, tile_index: usize) -> bool {
+        self.saved.get(tile_index).copied().unwrap_or(false)
+    }
+
+    /* Some(true) while RESUME is picked on the selected tile, Some(false) while NEW is */
+    pub fn choice(&self) -> Option<bool> {
+        self.choice
     }
 
     pub fn update(&mut self, input: &mut EnumMap<MenuInput, bool>, elapsed: u128) -> MenuStatus {
         let (button, mut movement) = self.get_movement(input);
         movement = self.handle_autoshift(button, movement, elapsed);
+        if self.choice.is_some() {
+            return self.update_choice(input, movement);
+        }
         self.selected_index = (self.selected_index as i32 + movement).rem_euclid(self.tiles.len() as i32) as usize;
 
         match (input[MenuInput::Accept], input[MenuInput::Cancel]) {
             (true, false) => {
                 input[MenuInput::Accept] = false;
                 match &self.tiles[self.selected_index] {
-                    MenuTile::Gamemode(name) => MenuStatus::Game(name.clone()),
+                    // A saved game can be resumed or thrown away for a new one
+                    MenuTile::Gamemode(_) if self.is_saved(self.selected_index) => {
+                        self.choice = Some(true);
+                        MenuStatus::Continue
+                    }
+                    MenuTile::Gamemode(name) => MenuStatus::Game { name: name.clone(), resume: false },
+                    MenuTile::Versus => MenuStatus::Versus,
+                    MenuTile::Online(role) => MenuStatus::Online(*role),
+                    MenuTile::Editor => MenuStatus::Editor,
                     MenuTile::Settings => MenuStatus::Settings,
                 }
             }
@@ -69,6 +113,33 @@ impl Menu {
         }
     }
 
+    /* Up and Left pick RESUME, Down and Right pick NEW. Cancel goes back to the tiles instead of exiting */
+    fn update_choice(&mut self, input: &mut EnumMap<MenuInput, bool>, movement: i32) -> MenuStatus {
+        if movement != 0 {
+            self.choice = Some(movement < 0);
+        }
+        match (input[MenuInput::Accept], input[MenuInput::Cancel]) {
+            (true, false) => {
+                input[MenuInput::Accept] = false;
+                let resume = self.choice.take().unwrap_or(true);
+                match &self.tiles[self.selected_index] {
+                    MenuTile::Gamemode(name) => MenuStatus::Game { name: name.clone(), resume },
+                    _ => MenuStatus::Continue,
+                }
+            }
+            (false, true) => {
+                input[MenuInput::Cancel] = false;
+                self.choice = None;
+                MenuStatus::Continue
+            }
+            (_, _) => MenuStatus::Continue,
+        }
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected_index
+    }
+
     fn get_movement(&mut self, input: &mut EnumMap<MenuInput, bool>) -> (MenuInput, i32) {
         /* Menu movement 
             Up and Down move one tile
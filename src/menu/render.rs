@@ -3,10 +3,22 @@ use super::{Menu, assets::MenuAssets};
 use sdl2::{pixels::Color, rect::Rect, render::{WindowCanvas, BlendMode}};
 
 pub fn render(menu: &Menu, canvas: &mut WindowCanvas, assets: &MenuAssets) -> Result<(), String>{
-    canvas.set_draw_color(Color::RGB(48, 64, 96));
+    canvas.set_draw_color(assets.background_color);
     canvas.clear();
     canvas.copy(&assets.menu_bg, None, None)?;
+    draw_tiles(menu, canvas, assets)
+}
+
+/* Draw the menu over what is already on the canvas, which is darkened by dim (0 to 255) so the tiles stand out */
+pub fn render_over(menu: &Menu, canvas: &mut WindowCanvas, assets: &MenuAssets, dim: u8) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, dim));
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.fill_rect(None)?;
+    canvas.set_blend_mode(BlendMode::None);
+    draw_tiles(menu, canvas, assets)
+}
 
+fn draw_tiles(menu: &Menu, canvas: &mut WindowCanvas, assets: &MenuAssets) -> Result<(), String> {
     let tile_width = 320;
     let tile_height = 64;
 
@@ -28,6 +40,28 @@ pub fn render(menu: &Menu, canvas: &mut WindowCanvas, assets: &MenuAssets) -> Re
         canvas.fill_rect(tile_dest)?;
         canvas.copy(&assets.menu_tile_overlay, None, tile_dest)?;
         canvas.copy(label, None, label_dest)?;
+        match menu.choice() {
+            Some(resume) if i == 0 => {
+                // Both options are stacked at the right of the tile, the one that is not picked is darkened
+                let resume_query = assets.resume_label.query();
+                let new_query = assets.new_label.query();
+                let resume_dest = Rect::new(x_pos + tile_width as i32 - resume_query.width as i32 - 12, y_pos + 12, resume_query.width, resume_query.height);
+                let new_dest = Rect::new(x_pos + tile_width as i32 - new_query.width as i32 - 12, y_pos + tile_height as i32 - new_query.height as i32 - 12, new_query.width, new_query.height);
+                canvas.copy(&assets.resume_label, None, resume_dest)?;
+                canvas.copy(&assets.new_label, None, new_dest)?;
+                canvas.set_draw_color(Color::RGBA(0, 0, 32, 164));
+                canvas.set_blend_mode(BlendMode::Blend);
+                canvas.fill_rect(if resume { new_dest } else { resume_dest })?;
+                canvas.set_blend_mode(BlendMode::None);
+            }
+            _ if menu.is_saved(tile_index as usize) => {
+                let query = assets.resume_label.query();
+                let resume_x = x_pos + tile_width as i32 - query.width as i32 - 12;
+                let resume_y = y_pos + (tile_height - query.height) as i32 / 2;
+                canvas.copy(&assets.resume_label, None, Rect::new(resume_x, resume_y, query.width, query.height))?;
+            }
+            _ => {}
+        }
         // Darken unselected tiles
         if i != 0 {
             canvas.set_draw_color(Color::RGBA(0, 0, 32, 164));
@@ -36,6 +70,5 @@ pub fn render(menu: &Menu, canvas: &mut WindowCanvas, assets: &MenuAssets) -> Re
             canvas.set_blend_mode(BlendMode::None);
         }
     }
-    canvas.present();
     Ok(())
 }
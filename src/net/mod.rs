@@ -0,0 +1,191 @@
+pub mod protocol;
+pub mod spectator;
+
+use protocol::NetMessage;
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver, Sender, TryRecvError}},
+    time::Duration,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum Role {
+    Host, // Waits for the other player to connect, and chooses the gamemode and seed
+    Join,
+}
+
+#[derive(Deserialize)]
+pub struct NetConfig {
+    pub host_address: String, // Address to listen on when hosting
+    pub join_address: String, // Address of the host to connect to when joining
+    pub gamemode: String, // Gamemode played when hosting
+}
+
+impl NetConfig {
+    pub fn load() -> Result<Self, String> {
+        crate::load_data_ron(std::path::Path::new("config/online.ron"))
+    }
+}
+
+enum NetEvent {
+    Connected(TcpStream),
+    Message(NetMessage),
+    Closed(String),
+}
+
+/* A connection to the other player. Connecting and reading happen on another thread so the game never waits on the network */
+pub struct Connection {
+    stream: Option<TcpStream>, // Used to send once connected
+    events: Receiver<NetEvent>,
+    stop: Arc<AtomicBool>, // Tells a host that is still waiting to stop listening
+    closed: Option<String>, // Why the connection ended
+}
+
+impl Connection {
+    pub fn host(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(address)
+            .map_err(|e| format!("Could not listen on {}: {}", address, e))?;
+        listener.set_nonblocking(true)
+            .map_err(|e| e.to_string())?;
+        let (sender, events) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        std::thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if stream.set_nonblocking(false).is_ok() {
+                            read_messages(stream, sender);
+                        }
+                        return;
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(50)),
+                    Err(e) => {
+                        let _ = sender.send(NetEvent::Closed(e.to_string()));
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(Self::new(events, stop))
+    }
+
+    pub fn join(address: &str) -> Self {
+        let address = address.to_string();
+        let (sender, events) = mpsc::channel();
+        std::thread::spawn(move || {
+            match TcpStream::connect(&address) {
+                Ok(stream) => read_messages(stream, sender),
+                Err(e) => {
+                    let _ = sender.send(NetEvent::Closed(format!("Could not connect to {}: {}", address, e)));
+                }
+            }
+        });
+        Self::new(events, Arc::new(AtomicBool::new(false)))
+    }
+
+    fn new(events: Receiver<NetEvent>, stop: Arc<AtomicBool>) -> Self {
+        Self {
+            stream: None,
+            events,
+            stop,
+            closed: None,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some() && self.closed.is_none()
+    }
+
+    /* Why the connection ended, if it has */
+    pub fn closed(&self) -> Option<&str> {
+        self.closed.as_deref()
+    }
+
+    /* Messages that arrived since this was last called */
+    pub fn receive(&mut self) -> Vec<NetMessage> {
+        let mut messages = Vec::new();
+        loop {
+            match self.events.try_recv() {
+                Ok(NetEvent::Connected(stream)) => self.stream = Some(stream),
+                Ok(NetEvent::Message(message)) => messages.push(message),
+                Ok(NetEvent::Closed(reason)) => self.close(reason),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if self.stream.is_some() {
+                        self.close(String::from("Connection lost"));
+                    }
+                    break;
+                }
+            }
+        }
+        messages
+    }
+
+    pub fn send(&mut self, message: &NetMessage) {
+        let stream = match &mut self.stream {
+            Some(x) if self.closed.is_none() => x,
+            _ => return,
+        };
+        let sent = serde_json::to_string(message)
+            .map_err(|e| e.to_string())
+            .and_then(|line| writeln!(stream, "{}", line).map_err(|e| e.to_string()));
+        if let Err(e) = sent {
+            self.close(format!("Could not send: {}", e));
+        }
+    }
+
+    fn close(&mut self, reason: String) {
+        if self.closed.is_none() {
+            self.closed = Some(reason);
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(stream) = &self.stream {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/* Hand a copy of the stream to the game for sending, then read messages until the connection closes */
+fn read_messages(stream: TcpStream, sender: Sender<NetEvent>) {
+    // Inputs are small and sent every tick, they should not wait to be combined
+    let _ = stream.set_nodelay(true);
+    match stream.try_clone() {
+        Ok(writer) => {
+            if sender.send(NetEvent::Connected(writer)).is_err() {
+                return;
+            }
+        }
+        Err(e) => {
+            let _ = sender.send(NetEvent::Closed(e.to_string()));
+            return;
+        }
+    }
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(x) => x,
+            Err(e) => {
+                let _ = sender.send(NetEvent::Closed(e.to_string()));
+                return;
+            }
+        };
+        match serde_json::from_str::<NetMessage>(&line) {
+            Ok(message) => {
+                if sender.send(NetEvent::Message(message)).is_err() {
+                    return;
+                }
+            }
+            Err(e) => eprintln!("Ignoring network message {}: {}", line, e),
+        }
+    }
+    let _ = sender.send(NetEvent::Closed(String::from("The other player disconnected")));
+}
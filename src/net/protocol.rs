@@ -0,0 +1,34 @@
+/* Messages between two players in online versus, sent as one JSON object per line */
+use crate::game::{Config, garbage::AttackTable};
+use crate::input::{GameInput, InputChange};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NetMessage {
+    // Sent by the player who joins as soon as they connect
+    Join {
+        version: String,
+        handling: Config,
+    },
+    // The host's answer, both players start simulating once it is sent
+    Start {
+        version: String,
+        gamemode: String,
+        seed: u64,
+        tick_length: u128, // µs simulated each tick, the host's tick rate is used by both players
+        handling: Config,
+        attack_table: AttackTable,
+    },
+    // The sender's inputs for every tick before until. Ticks that are not listed had no inputs
+    Inputs {
+        until: u64,
+        changes: Vec<(u64, Vec<InputChange<GameInput>>)>,
+    },
+    // Hash of both games after a tick, to find out if the players' simulations have drifted apart
+    Hash {
+        tick: u64,
+        hash: u64,
+    },
+}
@@ -0,0 +1,149 @@
+/* Publishes the state of a game as one JSON object per line to anyone who connects, for overlays and spectators */
+use crate::game::{Game, GameEvent, Matrix, Stats, piece::PieceColor};
+
+use std::{
+    io::Write,
+    net::TcpListener,
+    path::Path,
+    sync::mpsc::{self, SyncSender, TrySendError},
+};
+use serde::{Deserialize, Serialize};
+
+const VIEWER_BUFFER: usize = 64; // Snapshots waiting to be sent to a viewer before newer ones are skipped
+const PUBLISH_INTERVAL: u128 = 16667; // Shortest µs between snapshots of updates where nothing happened
+
+#[derive(Deserialize)]
+pub struct SpectatorConfig {
+    pub enabled: bool,
+    pub address: String, // Address viewers connect to, keep it on 127.0.0.1 unless others should be able to watch
+}
+
+impl SpectatorConfig {
+    pub fn load() -> Result<Self, String> {
+        crate::load_data_ron(Path::new("config/spectator.ron"))
+    }
+}
+
+/* Everything a viewer needs to draw the game after an update */
+#[derive(Serialize)]
+pub struct Snapshot<'a> {
+    matrix: &'a Matrix, // Rows from the top down, including the offscreen rows
+    offscreen_rows: usize, // Rows at the top of the matrix that are not shown
+    piece: PieceSnapshot<'a>,
+    hold: Option<&'a str>,
+    queue: Vec<&'a str>, // In the order the pieces will be played
+    level: usize,
+    stats: &'a Stats,
+    game_over: bool,
+    used_undo: bool,
+    events: &'a [GameEvent], // What happened during the update
+}
+
+#[derive(Serialize)]
+struct PieceSnapshot<'a> {
+    name: &'a str,
+    color: PieceColor,
+    rotation: usize, // 0 is the spawn orientation, then clockwise
+    col: i32,
+    row: i32,
+    ghost_row: i32,
+    cells: Vec<(i32, i32)>, // Matrix [col, row] of each block
+}
+
+impl<'a> Snapshot<'a> {
+    pub fn new(game: &'a Game, events: &'a [GameEvent]) -> Self {
+        let piece = game.current_piece();
+        Self {
+            matrix: game.matrix(),
+            offscreen_rows: crate::OFFSCREEN_ROWS,
+            piece: PieceSnapshot {
+                name: &piece.name,
+                color: piece.color,
+                rotation: piece.rotation(),
+                col: piece.position.col,
+                row: piece.position.row,
+                ghost_row: piece.ghost_position,
+                cells: piece.get_orientation().iter()
+                    .map(|(col, row)| (*col as i32 + piece.position.col, *row as i32 + piece.position.row))
+                    .collect(),
+            },
+            hold: game.held_piece().map(|x| x.name.as_str()),
+            queue: game.get_preview_pieces().iter().rev().map(|x| x.name.as_str()).collect(),
+            level: game.level(),
+            stats: game.stats(),
+            game_over: game.is_over(),
+            used_undo: game.used_undo(),
+            events,
+        }
+    }
+}
+
+/* Viewers each get their own thread to write to, so a slow viewer only misses snapshots instead of slowing the game */
+pub struct SpectatorServer {
+    listener: TcpListener,
+    viewers: Vec<SyncSender<String>>,
+    since_publish: u128, // µs since the last snapshot was sent
+}
+
+impl SpectatorServer {
+    pub fn new(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(address)
+            .map_err(|e| format!("Could not listen for spectators on {}: {}", address, e))?;
+        listener.set_nonblocking(true)
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            listener,
+            viewers: Vec::new(),
+            since_publish: 0,
+        })
+    }
+
+    /* Returns None when spectating is disabled. The game is still playable when the server can not start,
+       for example when another instance is already using the address */
+    pub fn from_config() -> Option<Self> {
+        let server = SpectatorConfig::load()
+            .and_then(|config| config.enabled.then(|| Self::new(&config.address)).transpose());
+        server.unwrap_or_else(|e| {
+            eprintln!("Spectating is unavailable: {}", e);
+            None
+        })
+    }
+
+    /* Updates with events are always sent, the rest at most once every PUBLISH_INTERVAL */
+    pub fn publish(&mut self, game: &Game, events: &[GameEvent], elapsed: u128) {
+        self.since_publish += elapsed;
+        if events.is_empty() && self.since_publish < PUBLISH_INTERVAL {
+            return;
+        }
+        self.since_publish = 0;
+        self.accept_viewers();
+        if self.viewers.is_empty() {
+            return;
+        }
+        let line = match serde_json::to_string(&Snapshot::new(game, events)) {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("Could not serialize snapshot: {}", e);
+                return;
+            }
+        };
+        self.viewers.retain(|viewer| !matches!(viewer.try_send(line.clone()), Err(TrySendError::Disconnected(_))));
+    }
+
+    fn accept_viewers(&mut self) {
+        while let Ok((mut stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(false).is_err() {
+                continue;
+            }
+            let (sender, lines) = mpsc::sync_channel::<String>(VIEWER_BUFFER);
+            std::thread::spawn(move || {
+                for line in lines {
+                    if writeln!(stream, "{}", line).is_err() {
+                        break;
+                    }
+                }
+            });
+            self.viewers.push(sender);
+        }
+    }
+}
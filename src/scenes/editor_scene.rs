@@ -0,0 +1,250 @@
+use super::{Scene, SceneAction, SceneTrait};
+use super::game_scene::GameScene;
+use crate::{
+    game::{Game, effects::Effects, layout::Layout, piece::PieceColor, render::{self, InputOverlay}, setup::Setup},
+    assets::Assets,
+    audio::{Audio, Sound},
+    input::{self, EditorInput, TimedEvent},
+    OFFSCREEN_ROWS,
+};
+
+use std::{collections::HashMap, path::{Path, PathBuf}};
+use enum_map::EnumMap;
+use serde::Deserialize;
+use sdl2::{rect::Rect, render::WindowCanvas};
+
+#[derive(Deserialize)]
+struct EditorConfig {
+    gamemode: String, // Gamemode of new positions
+    directory: String, // Where positions are saved and loaded
+}
+
+/* Paint the matrix and choose the queue and hold piece, then play from that position.
+   The position is shown as a game that has not started yet, so it looks the same as when it is played */
+pub struct EditorScene {
+    config: EditorConfig,
+    bindings: HashMap<String, EditorInput>,
+    inputs: EnumMap<EditorInput, bool>,
+    setup: Setup,
+    preview: Game, // Changed along with the setup, only made again when a position is loaded
+    seed: u64, // Kept for the preview so the pieces after the queue stay the same between changes
+    effects: Effects,
+    layout: Layout,
+    input_overlay: InputOverlay,
+    cursor: (usize, usize), // Column and visible row
+    brush: usize, // Index in the piece list, one past the end is garbage
+    file: Option<PathBuf>, // Where the position was loaded from or last saved to
+    message: Option<String>, // Result of the last save or load
+}
+
+impl EditorScene {
+    pub fn new() -> Result<Self, String> {
+        let config: EditorConfig = crate::load_data_ron(Path::new("config/editor.ron"))?;
+        let setup = Setup::new(config.gamemode.clone());
+        let seed = rand::random();
+        let preview = Game::from_setup(&setup, seed)?;
+        let layout = crate::load_data_ron(Path::new(&format!("data/layouts/{}.ron", preview.layout_name())))?;
+        Ok(Self {
+            config,
+            bindings: crate::load_data(Path::new("config/editor_control_config.toml"))?,
+            inputs: EnumMap::default(),
+            setup,
+            preview,
+            seed,
+            effects: Effects::new(crate::load_data_ron(Path::new("config/effects.ron"))?),
+            layout,
+            input_overlay: crate::load_data_ron(Path::new("config/input_overlay.ron"))?,
+            cursor: (0, 0),
+            brush: 0,
+            file: None,
+            message: None,
+        })
+    }
+
+    /* Switch to a loaded setup, which is thrown away if a game can not start from it */
+    fn apply(&mut self, setup: Setup) -> Result<(), String> {
+        let preview = Game::from_setup(&setup, self.seed)?;
+        if preview.layout_name() != self.preview.layout_name() {
+            self.layout = crate::load_data_ron(Path::new(&format!("data/layouts/{}.ron", preview.layout_name())))?;
+        }
+        self.setup = setup;
+        self.preview = preview;
+        self.brush = self.brush.min(self.preview.piece_list().len());
+        let (columns, rows) = self.visible_size();
+        self.cursor = (self.cursor.0.min(columns - 1), self.cursor.1.min(rows - 1));
+        Ok(())
+    }
+
+    fn visible_size(&self) -> (usize, usize) {
+        let matrix = self.preview.matrix();
+        (matrix[0].len(), matrix.len() - OFFSCREEN_ROWS)
+    }
+
+    /* The brush's piece, None for garbage */
+    fn brush_piece(&self) -> Option<&String> {
+        self.preview.piece_list().get(self.brush)
+    }
+
+    fn brush_color(&self) -> PieceColor {
+        match self.brush_piece() {
+            Some(name) => self.preview.piece_color(name).unwrap_or(PieceColor::Gray),
+            None => PieceColor::Gray,
+        }
+    }
+
+    fn paint(&mut self, color: PieceColor) {
+        let (col, row) = (self.cursor.0, self.cursor.1 + OFFSCREEN_ROWS);
+        if self.preview.matrix()[row][col] != color {
+            self.preview.set_block(col, row, color);
+            self.setup.set_rows(self.preview.matrix());
+        }
+    }
+
+    /* Queue and hold changes use the pieces already loaded for the preview */
+    fn change_pieces(&mut self, change: impl FnOnce(&mut Setup)) {
+        let previous = self.setup.clone();
+        change(&mut self.setup);
+        self.preview.change_setup_pieces(&previous, &self.setup);
+    }
+
+    fn edit(&mut self, input: EditorInput) -> Result<Option<SceneAction>, String> {
+        let (columns, rows) = self.visible_size();
+        let brushes = self.preview.piece_list().len() + 1;
+        match input {
+            EditorInput::Up => self.cursor.1 = (self.cursor.1 + rows - 1) % rows,
+            EditorInput::Down => self.cursor.1 = (self.cursor.1 + 1) % rows,
+            EditorInput::Left => self.cursor.0 = (self.cursor.0 + columns - 1) % columns,
+            EditorInput::Right => self.cursor.0 = (self.cursor.0 + 1) % columns,
+            EditorInput::NextBrush => self.brush = (self.brush + 1) % brushes,
+            EditorInput::PreviousBrush => self.brush = (self.brush + brushes - 1) % brushes,
+            EditorInput::AddToQueue => {
+                if let Some(name) = self.brush_piece().cloned() {
+                    self.change_pieces(|setup| setup.queue.push(name));
+                }
+            }
+            EditorInput::RemoveFromQueue => self.change_pieces(|setup| {
+                setup.queue.pop();
+            }),
+            EditorInput::Hold => {
+                let piece = self.brush_piece().cloned();
+                self.change_pieces(|setup| setup.hold = if setup.hold == piece { None } else { piece });
+            }
+            EditorInput::Save => self.save()?,
+            EditorInput::Load => self.load_next()?,
+            EditorInput::Play => {
+                return Ok(Some(SceneAction::Push(Scene::Game(GameScene::from_setup(self.setup.clone())?))));
+            }
+            EditorInput::Exit => return Ok(Some(SceneAction::Pop)),
+            EditorInput::Paint | EditorInput::Erase => {}
+        }
+        Ok(None)
+    }
+
+    /* Save over the file the position came from, or to a new numbered file */
+    fn save(&mut self) -> Result<(), String> {
+        let path = match &self.file {
+            Some(x) => x.clone(),
+            None => {
+                let directory = Path::new(&self.config.directory);
+                (1..).map(|i| directory.join(format!("position_{}.ron", i))).find(|x| !x.exists()).unwrap()
+            }
+        };
+        self.setup.save(&path)?;
+        self.message = Some(format!("SAVED {}", path.display()));
+        self.file = Some(path);
+        Ok(())
+    }
+
+    /* Positions are loaded in order of their file names, starting over after the last one */
+    fn load_next(&mut self) -> Result<(), String> {
+        let directory = Path::new(&self.config.directory);
+        let mut files = std::fs::read_dir(directory)
+            .map_err(|e| format!("Error opening {}: {}", directory.display(), e))?
+            .filter_map(|entry| entry.ok().map(|x| x.path()))
+            .filter(|path| path.extension().is_some_and(|x| x == "ron"))
+            .collect::<Vec<_>>();
+        files.sort();
+        let next = match &self.file {
+            Some(current) => files.iter().find(|x| *x > current).or_else(|| files.first()),
+            None => files.first(),
+        };
+        let path = match next {
+            Some(x) => x.clone(),
+            None => return Err(format!("There are no positions in {}", directory.display())),
+        };
+        let setup = Setup::load(&path)?;
+        self.apply(setup)?;
+        self.message = Some(format!("LOADED {}", path.display()));
+        self.file = Some(path);
+        Ok(())
+    }
+}
+
+impl SceneTrait for EditorScene {
+    fn handle_input(&mut self, input_events: Vec<TimedEvent>) {
+        for event in input_events {
+            input::handle_input_event(&mut self.inputs, event.event, &self.bindings);
+        }
+    }
+
+    fn update(&mut self, _elapsed: u128, audio: &mut Audio) -> SceneAction {
+        // Painting continues while the cursor moves, everything else happens once for each press
+        let pressed = self.inputs.iter()
+            .find(|(input, pressed)| **pressed && !matches!(input, EditorInput::Paint | EditorInput::Erase))
+            .map(|(input, _)| input);
+        let action = match pressed {
+            Some(input) => {
+                self.inputs[input] = false;
+                self.edit(input)
+            }
+            None => Ok(None),
+        };
+        if action.is_ok() {
+            if self.inputs[EditorInput::Paint] {
+                self.paint(self.brush_color());
+            } else if self.inputs[EditorInput::Erase] {
+                self.paint(PieceColor::Empty);
+            }
+        }
+
+        match action {
+            Ok(Some(action)) => {
+                audio.play(Sound::MenuSelect);
+                action
+            }
+            Ok(None) => SceneAction::Continue,
+            Err(e) => {
+                eprintln!("{}", e);
+                self.message = Some(e);
+                SceneAction::Continue
+            }
+        }
+    }
+
+    fn render(&self, canvas: &mut WindowCanvas, assets: &mut Assets) -> Result<(), String> {
+        let assets = assets.get_game_assets(&self.setup.gamemode, self.preview.background())?;
+        render::render(canvas, &self.preview, &self.effects, assets, &self.layout, &self.input_overlay, &EnumMap::default())?;
+
+        // The cursor shows the brush's color over the square it is on
+        let square = render::matrix_square(&self.preview, &self.layout, self.cursor.0 as i32, self.cursor.1 as i32);
+        assets.block_sheet.set_alpha_mod(160);
+        canvas.copy(&assets.block_sheet, assets.block_sprites[self.brush_color() as usize], square)?;
+        assets.block_sheet.set_alpha_mod(255);
+        canvas.set_draw_color(assets.hint_color);
+        canvas.draw_rect(square)?;
+
+        let brush = match self.brush_piece() {
+            Some(name) => format!("BRUSH {}", name),
+            None => String::from("BRUSH GARBAGE"),
+        };
+        let mut y = crate::display::LOGICAL_HEIGHT as i32 - 8;
+        for line in self.message.iter().chain(std::iter::once(&brush)) {
+            let text = assets.popup_texture(line)?;
+            text.set_alpha_mod(255);
+            let query = text.query();
+            y -= query.height as i32;
+            canvas.copy(text, None, Rect::new(8, y, query.width, query.height))?;
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,12 @@
 use super::{SceneAction, SceneTrait};
 use crate::{
-    game::{Game, render},
+    game::{Game, GameEvent, effects::Effects, layout::Layout, render::{self, InputOverlay}},
     assets::Assets,
-    input::{self, GameInput},
+    audio::Audio,
+    bot::{BotConfig, BotPlayer},
+    input::{self, GameInput, InputChange, TimedEvent},
+    net::spectator::SpectatorServer,
+    game::setup::Setup,
 };
 
 use std::{collections::HashMap, path::Path};
@@ -13,39 +17,135 @@ pub struct GameScene {
     bindings: HashMap<String, GameInput>,
 
     game: Game,
+    effects: Effects,
+    layout: Layout,
     inputs: EnumMap<GameInput, bool>,
+    press_times: EnumMap<GameInput, u32>,
+    input_changes: Vec<InputChange<GameInput>>,
+    input_overlay: InputOverlay,
     gamemode_name: String,
+    paused: bool,
+    bot: Option<BotPlayer>, // Plays instead of the keyboard when enabled
+    spectators: Option<SpectatorServer>, // Kept across resets so viewers stay connected
+    setup: Option<Setup>, // Position made in the editor that resets go back to
 }
 
 impl GameScene {
     pub fn new(gamemode_name: String) -> Result<Self, String> {
+        Self::with_game(Game::new(&gamemode_name)?, gamemode_name, SpectatorServer::from_config())
+    }
+
+    /* Continue the game that was saved when the program last closed */
+    pub fn resume(gamemode_name: String) -> Result<Self, String> {
+        Self::with_game(Game::resume(&gamemode_name)?, gamemode_name, SpectatorServer::from_config())
+    }
+
+    /* Play from a position made in the editor */
+    pub fn from_setup(setup: Setup) -> Result<Self, String> {
+        let game = Game::from_setup(&setup, rand::random())?;
+        let mut scene = Self::with_game(game, setup.gamemode.clone(), SpectatorServer::from_config())?;
+        scene.setup = Some(setup);
+        Ok(scene)
+    }
+
+    fn with_game(game: Game, gamemode_name: String, spectators: Option<SpectatorServer>) -> Result<Self, String> {
+        let layout = crate::load_data_ron(Path::new(&format!("data/layouts/{}.ron", game.layout_name())))?;
+        let bot_config = BotConfig::load()?;
+        let bot = if bot_config.enabled { Some(BotPlayer::new(&bot_config)?) } else { None };
         Ok(Self {
             bindings: crate::load_data(Path::new("config/control_config.toml"))?,
-            game: Game::new(&gamemode_name)?,
+            game,
+            effects: Effects::new(crate::load_data_ron(Path::new("config/effects.ron"))?),
+            layout,
             inputs: EnumMap::default(),
+            press_times: EnumMap::default(),
+            input_changes: Vec::new(),
+            input_overlay: crate::load_data_ron(Path::new("config/input_overlay.ron"))?,
             gamemode_name,
+            paused: false,
+            bot,
+            spectators,
+            setup: None,
         })
     }
 }
 
 impl SceneTrait for GameScene {
-    fn handle_input(&mut self, input_events: Vec<sdl2::event::Event>) {
+    fn handle_input(&mut self, input_events: Vec<TimedEvent>) {
         for event in input_events {
-            input::handle_input_event(&mut self.inputs, event, &self.bindings);
+            if let Some(change) = input::read_timed_input_event(&event, &self.bindings) {
+                self.input_changes.push(change);
+            }
         }
     }
 
-    fn update(&mut self, elapsed: u128) -> SceneAction {
-        if self.inputs[GameInput::Reset] {
-            *self = GameScene::new(std::mem::take(&mut self.gamemode_name)).expect("Reset Error");
+    fn update(&mut self, elapsed: u128, audio: &mut Audio) -> SceneAction {
+        let mut input_changes = std::mem::take(&mut self.input_changes);
+        if self.bot.is_some() {
+            // Only pausing and resetting are left to the keyboard
+            input_changes.retain(|x| matches!(x.input, GameInput::Reset | GameInput::Pause));
+        }
+        let pause_pressed = input_changes.iter().any(|x| matches!(x.input, GameInput::Pause) && x.pressed && !self.inputs[GameInput::Pause]);
+        if pause_pressed {
+            self.paused = !self.paused;
+            audio.pause_music(self.paused);
+        }
+        if self.paused {
+            // Keep track of held inputs without simulating, so they are correct when the game resumes
+            for change in input_changes.iter() {
+                input::apply_input_change(&mut self.inputs, &mut self.press_times, change);
+            }
             return SceneAction::Continue;
         }
 
-        self.game.update(&mut self.inputs, elapsed);
+        if let Some(bot) = &mut self.bot {
+            // The bot's inputs happen at the start of the update, so they go before the keyboard's
+            let mut bot_changes = bot.update(&self.game);
+            bot_changes.append(&mut input_changes);
+            input_changes = bot_changes;
+        }
+        self.game.update(&mut self.inputs, &mut self.press_times, &input_changes, elapsed);
+        let events = self.game.take_events();
+        for event in events.iter() {
+            if let GameEvent::LinesCleared(line_clear) = event {
+                self.effects.add_line_clear(line_clear);
+            }
+        }
+        audio.play_game_events(&events);
+        if let Some(bot) = &mut self.bot {
+            bot.handle_events(&events);
+        }
+        if let Some(spectators) = &mut self.spectators {
+            spectators.publish(&self.game, &events, elapsed);
+        }
+        self.effects.update(elapsed);
+
+        if self.inputs[GameInput::Reset] {
+            let gamemode_name = std::mem::take(&mut self.gamemode_name);
+            let setup = self.setup.take();
+            let game = match &setup {
+                Some(setup) => Game::from_setup(setup, rand::random()),
+                None => Game::new(&gamemode_name),
+            }.expect("Reset Error");
+            *self = GameScene::with_game(game, gamemode_name, self.spectators.take()).expect("Reset Error");
+            self.setup = setup;
+            audio.pause_music(false);
+        }
         SceneAction::Continue
     }
 
+    /* Unfinished games are saved so they can be resumed from the menu.
+       Games from the editor are not, they would replace the save of a normal game of the same gamemode */
+    fn quit(&mut self) {
+        if self.game.is_over() || self.setup.is_some() {
+            return;
+        }
+        if let Err(e) = self.game.save(&self.gamemode_name) {
+            eprintln!("{}", e);
+        }
+    }
+
     fn render(&self, canvas: &mut sdl2::render::WindowCanvas, assets: &mut Assets) -> Result<(), String> {
-        render::render(canvas, &self.game, assets.get_game_assets(&self.gamemode_name)?)
+        render::render(canvas, &self.game, &self.effects, assets.get_game_assets(&self.gamemode_name, self.game.background())?, &self.layout, &self.input_overlay, &self.inputs)
     }
 }
\ No newline at end of file
@@ -1,20 +1,42 @@
 use super::{Scene, SceneAction, SceneTrait};
 use super::game_scene::GameScene;
+use super::versus_scene::VersusScene;
+use super::online_scene::OnlineScene;
+use super::editor_scene::EditorScene;
 
 use crate::{
     menu::{self, Menu, MenuStatus},
+    game::{Game, GameEvent, effects::Effects, layout::Layout, render::{self, InputOverlay}, save},
     assets::Assets,
-    input::{self, MenuInput},
+    audio::{Audio, Sound},
+    bot::demo::DemoPlayer,
+    input::{self, GameInput, MenuInput, TimedEvent},
 };
 
 use std::{collections::HashMap, path::Path};
 use enum_map::EnumMap;
-use sdl2::render::WindowCanvas;
+use serde::Deserialize;
+use sdl2::{event::Event, render::WindowCanvas};
+
+/* Demos are played behind the menu after it sits idle */
+#[derive(Deserialize)]
+struct DemoConfig {
+    enabled: bool,
+    idle_time: u32, // Milliseconds without input before a demo starts
+    duration: u32, // Milliseconds each demo plays before moving on to the next gamemode
+    move_delay: u32, // Milliseconds the player waits before placing each piece
+    dim: u8, // How much the demo is darkened behind the menu, 0 to 255
+    gamemodes: Vec<String>, // Played in order, then repeated
+}
 
 pub struct MenuScene {
     menu: Menu,
     bindings: HashMap<String, MenuInput>,
     inputs: EnumMap<MenuInput, bool>,
+    demo_config: DemoConfig,
+    demo: Option<Demo>,
+    idle_timer: u128,
+    next_demo: usize, // Index in the demo config of the gamemode to play next
 }
 
 impl MenuScene {
@@ -24,28 +46,189 @@ impl MenuScene {
             bindings: crate::load_data(Path::new("config/menu_control_config.toml"))?,
             menu: Menu::new()?,
             inputs: EnumMap::default(),
+            demo_config: crate::load_data_ron(Path::new("config/demo.ron"))?,
+            demo: None,
+            idle_timer: 0,
+            next_demo: 0,
         })
     }
+
+    fn update_demo(&mut self, elapsed: u128) {
+        if !self.demo_config.enabled || self.demo_config.gamemodes.is_empty() {
+            return;
+        }
+        match &mut self.demo {
+            Some(demo) => {
+                demo.update(elapsed);
+                if demo.game.is_over() || demo.time >= self.demo_config.duration as u128 * 1000 {
+                    self.start_demo();
+                }
+            }
+            None => {
+                self.idle_timer += elapsed;
+                if self.idle_timer >= self.demo_config.idle_time as u128 * 1000 {
+                    self.start_demo();
+                }
+            }
+        }
+    }
+
+    fn start_demo(&mut self) {
+        let gamemode_name = &self.demo_config.gamemodes[self.next_demo];
+        self.next_demo = (self.next_demo + 1) % self.demo_config.gamemodes.len();
+        self.idle_timer = 0;
+        self.demo = match Demo::new(gamemode_name.to_string(), self.demo_config.move_delay as u128 * 1000) {
+            Ok(x) => Some(x),
+            Err(e) => {
+                eprintln!("Could not start demo of {}: {}", gamemode_name, e);
+                None
+            }
+        };
+    }
 }
 
 impl SceneTrait for MenuScene {
-    fn handle_input(&mut self, input_events: Vec<sdl2::event::Event>) {
-        for event in input_events {
-            input::handle_input_event(&mut self.inputs, event, &self.bindings);
+    fn handle_input(&mut self, input_events: Vec<TimedEvent>) {
+        for timed_event in input_events {
+            if matches!(timed_event.event, Event::KeyDown { .. } | Event::ControllerButtonDown { .. }) {
+                self.idle_timer = 0;
+                // The press that stops a demo only returns to the menu
+                if self.demo.take().is_some() {
+                    continue;
+                }
+            }
+            input::handle_input_event(&mut self.inputs, timed_event.event, &self.bindings);
         }
     }
 
-    fn update(&mut self, elapsed: u128) -> SceneAction {
-        match self.menu.update(&mut self.inputs, elapsed) {
+    fn update(&mut self, elapsed: u128, audio: &mut Audio) -> SceneAction {
+        audio.play_menu_music();
+        self.update_demo(elapsed);
+        let previous_index = self.menu.selected_index();
+        let status = self.menu.update(&mut self.inputs, elapsed);
+        if self.menu.selected_index() != previous_index {
+            audio.play(Sound::MenuMove);
+        }
+        match status {
             MenuStatus::Continue => SceneAction::Continue,
-            MenuStatus::Game(mode) => SceneAction::Push(Scene::Game(GameScene::new(mode).unwrap())),
-            MenuStatus::Settings => SceneAction::Continue,
-            MenuStatus::Exit => SceneAction::Pop,
+            MenuStatus::Game { name: mode, resume } => {
+                audio.play(Sound::MenuSelect);
+                self.demo = None;
+                self.idle_timer = 0;
+                let scene = match resume {
+                    true => GameScene::resume(mode.clone()).or_else(|e| {
+                        eprintln!("Could not resume {}: {}", mode, e);
+                        GameScene::new(mode)
+                    }),
+                    false => {
+                        // Starting over throws the saved game away, so it is not offered again
+                        if let Err(e) = save::delete_save(&mode) {
+                            eprintln!("{}", e);
+                        }
+                        GameScene::new(mode)
+                    }
+                };
+                self.menu.refresh_saves();
+                SceneAction::Push(Scene::Game(scene.unwrap()))
+            }
+            MenuStatus::Versus => {
+                audio.play(Sound::MenuSelect);
+                self.demo = None;
+                self.idle_timer = 0;
+                SceneAction::Push(Scene::Versus(VersusScene::new().unwrap()))
+            }
+            MenuStatus::Online(role) => {
+                audio.play(Sound::MenuSelect);
+                self.demo = None;
+                self.idle_timer = 0;
+                match OnlineScene::new(role) {
+                    Ok(scene) => SceneAction::Push(Scene::Online(scene)),
+                    Err(e) => {
+                        eprintln!("Could not start online versus: {}", e);
+                        SceneAction::Continue
+                    }
+                }
+            }
+            MenuStatus::Editor => {
+                audio.play(Sound::MenuSelect);
+                self.demo = None;
+                self.idle_timer = 0;
+                match EditorScene::new() {
+                    Ok(scene) => SceneAction::Push(Scene::Editor(scene)),
+                    Err(e) => {
+                        eprintln!("Could not start the editor: {}", e);
+                        SceneAction::Continue
+                    }
+                }
+            }
+            MenuStatus::Settings => {
+                audio.play(Sound::MenuSelect);
+                SceneAction::Continue
+            }
+            MenuStatus::Exit => {
+                audio.play(Sound::MenuBack);
+                SceneAction::Pop
+            }
         }
     }
 
     fn render(&self, canvas: &mut WindowCanvas, assets: &mut Assets) -> Result<(), String> {
-        menu::render::render(&self.menu, canvas, assets.get_menu_assets()?)?;
+        match &self.demo {
+            Some(demo) => {
+                demo.render(canvas, assets)?;
+                menu::render::render_over(&self.menu, canvas, assets.get_menu_assets()?, self.demo_config.dim)?;
+            }
+            None => menu::render::render(&self.menu, canvas, assets.get_menu_assets()?)?,
+        }
         Ok(())
     }
 }
+
+/* A game played by the demo player. It is silent so that the menu music keeps playing */
+struct Demo {
+    game: Game,
+    player: DemoPlayer,
+    effects: Effects,
+    layout: Layout,
+    input_overlay: InputOverlay,
+    inputs: EnumMap<GameInput, bool>,
+    press_times: EnumMap<GameInput, u32>,
+    gamemode_name: String,
+    time: u128,
+}
+
+impl Demo {
+    fn new(gamemode_name: String, move_delay: u128) -> Result<Self, String> {
+        let game = Game::new(&gamemode_name)?;
+        let layout = crate::load_data_ron(Path::new(&format!("data/layouts/{}.ron", game.layout_name())))?;
+        Ok(Self {
+            game,
+            player: DemoPlayer::new(move_delay)?,
+            effects: Effects::new(crate::load_data_ron(Path::new("config/effects.ron"))?),
+            layout,
+            input_overlay: crate::load_data_ron(Path::new("config/input_overlay.ron"))?,
+            inputs: EnumMap::default(),
+            press_times: EnumMap::default(),
+            gamemode_name,
+            time: 0,
+        })
+    }
+
+    fn update(&mut self, elapsed: u128) {
+        self.time += elapsed;
+        let input_changes = self.player.update(&self.game, elapsed);
+        self.game.update(&mut self.inputs, &mut self.press_times, &input_changes, elapsed);
+        let events = self.game.take_events();
+        for event in events.iter() {
+            if let GameEvent::LinesCleared(line_clear) = event {
+                self.effects.add_line_clear(line_clear);
+            }
+        }
+        self.player.handle_events(&events);
+        self.effects.update(elapsed);
+    }
+
+    fn render(&self, canvas: &mut WindowCanvas, assets: &mut Assets) -> Result<(), String> {
+        render::render(canvas, &self.game, &self.effects, assets.get_game_assets(&self.gamemode_name, self.game.background())?, &self.layout, &self.input_overlay, &self.inputs)
+    }
+}
@@ -1,15 +1,22 @@
 pub mod game_scene;
 pub mod menu_scene;
+pub mod versus_scene;
+pub mod online_scene;
+pub mod editor_scene;
 
 use crate::assets::Assets;
+use crate::audio::Audio;
+use crate::input::TimedEvent;
 
 use sdl2::render::WindowCanvas;
 
 pub trait SceneTrait {
     // fn start();
-    fn handle_input(&mut self, input_events: Vec<sdl2::event::Event>);
-    fn update(&mut self, elapsed: u128) -> SceneAction;
+    fn handle_input(&mut self, input_events: Vec<TimedEvent>);
+    fn update(&mut self, elapsed: u128, audio: &mut Audio) -> SceneAction;
     fn render(&self, canvas: &mut WindowCanvas, assets: &mut Assets) -> Result<(), String>;
+    // Called on every scene in the stack when the program closes
+    fn quit(&mut self) {}
 }
 
 pub enum SceneAction {
@@ -22,6 +29,9 @@ pub enum SceneAction {
 pub enum Scene {
     Game(game_scene::GameScene),
     MainMenu(menu_scene::MenuScene),
+    Versus(versus_scene::VersusScene),
+    Online(online_scene::OnlineScene),
+    Editor(editor_scene::EditorScene),
     // Settings,
 }
 
@@ -48,21 +58,46 @@ impl SceneManager {
         }
     }
 
-    pub fn update(&mut self, canvas: &mut WindowCanvas, assets: &mut Assets, input_events: Vec<sdl2::event::Event>, elapsed: u128) -> Result<(), String> {
+    pub fn update(&mut self, input_events: Vec<TimedEvent>, elapsed: u128, audio: &mut Audio) {
         let next = match self.stack.last_mut().unwrap() {
-            Scene::Game(game) => SceneManager::run_scene(game, canvas, assets, input_events, elapsed)?,
-            Scene::MainMenu(menu) => SceneManager::run_scene(menu, canvas, assets, input_events, elapsed)?,
+            Scene::Game(game) => SceneManager::update_scene(game, input_events, elapsed, audio),
+            Scene::MainMenu(menu) => SceneManager::update_scene(menu, input_events, elapsed, audio),
+            Scene::Versus(versus) => SceneManager::update_scene(versus, input_events, elapsed, audio),
+            Scene::Online(online) => SceneManager::update_scene(online, input_events, elapsed, audio),
+            Scene::Editor(editor) => SceneManager::update_scene(editor, input_events, elapsed, audio),
         };
 
         self.handle_scene_action(next);
+    }
+
+    /* Scenes only draw, so that a scene can draw other things beneath itself before the frame is shown */
+    pub fn render(&self, canvas: &mut WindowCanvas, assets: &mut Assets) -> Result<(), String> {
+        match self.stack.last().unwrap() {
+            Scene::Game(game) => game.render(canvas, assets)?,
+            Scene::MainMenu(menu) => menu.render(canvas, assets)?,
+            Scene::Versus(versus) => versus.render(canvas, assets)?,
+            Scene::Online(online) => online.render(canvas, assets)?,
+            Scene::Editor(editor) => editor.render(canvas, assets)?,
+        }
+        canvas.present();
         Ok(())
     }
 
-    fn run_scene<T>(scene: &mut T, canvas: &mut WindowCanvas, assets: &mut Assets, input_events: Vec<sdl2::event::Event>, elapsed: u128) -> Result<SceneAction, String>
+    pub fn quit(&mut self) {
+        for scene in self.stack.iter_mut() {
+            match scene {
+                Scene::Game(game) => game.quit(),
+                Scene::MainMenu(menu) => menu.quit(),
+                Scene::Versus(versus) => versus.quit(),
+                Scene::Online(online) => online.quit(),
+                Scene::Editor(editor) => editor.quit(),
+            }
+        }
+    }
+
+    fn update_scene<T>(scene: &mut T, input_events: Vec<TimedEvent>, elapsed: u128, audio: &mut Audio) -> SceneAction
     where T: SceneTrait {
         scene.handle_input(input_events);
-        let action = scene.update(elapsed);
-        scene.render(canvas, assets)?;
-        Ok(action)
+        scene.update(elapsed, audio)
     }
 }
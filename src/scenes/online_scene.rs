@@ -0,0 +1,291 @@
+use super::{SceneAction, SceneTrait};
+use super::versus_scene::{self, Outcome, Player, VersusConfig};
+use crate::{
+    game::{Config, Game, garbage::AttackTable, layout::Layout, render::InputOverlay},
+    assets::Assets,
+    audio::Audio,
+    input::{self, GameInput, InputChange, TimedEvent},
+    net::{Connection, NetConfig, Role, protocol::NetMessage},
+};
+
+use std::{collections::{HashMap, VecDeque}, path::Path};
+use sdl2::{pixels::Color, rect::Rect, render::WindowCanvas};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const INPUT_DELAY: u128 = 80000; // µs between an input and when it is simulated, so it has time to reach the other player
+const SEND_INTERVAL: u128 = 16000; // Longest µs between sending inputs when there are none
+const HASH_INTERVAL: u64 = 60; // Ticks between comparing hashes of the games
+
+/* Versus against a player on another machine. Both players simulate both games from the same seed,
+   and a tick is only simulated once both players' inputs for it have arrived */
+pub struct OnlineScene {
+    role: Role,
+    net_config: NetConfig,
+    connection: Connection,
+    bindings: HashMap<String, GameInput>,
+    local_changes: Vec<InputChange<GameInput>>, // Inputs that have not been sent yet
+    sent_join: bool,
+    versus: Option<OnlineMatch>,
+    ended: Option<String>, // Why the match can not continue
+}
+
+struct OnlineMatch {
+    gamemode: String,
+    attack_table: AttackTable,
+    tick_length: u128,
+    players: [Player; 2], // The host is player 0
+    local: usize,
+    layout: Layout,
+    input_overlay: InputOverlay,
+    pending: [VecDeque<Vec<InputChange<GameInput>>>; 2], // Each player's inputs for the ticks starting at tick
+    tick: u64, // Next tick to simulate
+    input_delay: u64, // INPUT_DELAY in ticks
+    send_interval: u64, // SEND_INTERVAL in ticks
+    local_tick: u64, // Next tick to take local inputs for
+    unsent: Vec<(u64, Vec<InputChange<GameInput>>)>, // Local ticks with inputs since the last message
+    sent_tick: u64, // First local tick that has not been sent
+    local_hashes: HashMap<u64, u64>,
+    remote_hashes: HashMap<u64, u64>,
+    outcome: Option<Outcome>,
+}
+
+impl OnlineScene {
+    pub fn new(role: Role) -> Result<Self, String> {
+        let net_config = NetConfig::load()?;
+        let connection = match role {
+            Role::Host => Connection::host(&net_config.host_address)?,
+            Role::Join => Connection::join(&net_config.join_address),
+        };
+        Ok(Self {
+            role,
+            net_config,
+            connection,
+            bindings: crate::load_data(Path::new("config/control_config.toml"))?,
+            local_changes: Vec::new(),
+            sent_join: false,
+            versus: None,
+            ended: None,
+        })
+    }
+
+    fn handle_message(&mut self, message: NetMessage, tick_length: u128) -> Result<(), String> {
+        match message {
+            NetMessage::Join { version, handling } if self.role == Role::Host && self.versus.is_none() => {
+                check_version(&version)?;
+                let seed = rand::random();
+                let local_handling = Config::load()?;
+                let attack_table = VersusConfig::load()?.attack_table;
+                self.connection.send(&NetMessage::Start {
+                    version: VERSION.to_string(),
+                    gamemode: self.net_config.gamemode.clone(),
+                    seed,
+                    tick_length,
+                    handling: local_handling.clone(),
+                    attack_table: attack_table.clone(),
+                });
+                let gamemode = self.net_config.gamemode.clone();
+                self.versus = Some(OnlineMatch::new(gamemode, seed, [local_handling, handling], attack_table, tick_length, 0)?);
+            }
+            NetMessage::Start { version, gamemode, seed, tick_length, handling, attack_table } if self.role == Role::Join && self.versus.is_none() => {
+                check_version(&version)?;
+                self.versus = Some(OnlineMatch::new(gamemode, seed, [handling, Config::load()?], attack_table, tick_length, 1)?);
+            }
+            NetMessage::Inputs { until, changes } => {
+                if let Some(versus) = &mut self.versus {
+                    versus.receive_inputs(until, changes)?;
+                }
+            }
+            NetMessage::Hash { tick, hash } => {
+                if let Some(versus) = &mut self.versus {
+                    versus.remote_hashes.insert(tick, hash);
+                    versus.check_hashes()?;
+                }
+            }
+            _ => return Err(String::from("Unexpected message from the other player")),
+        }
+        Ok(())
+    }
+
+    fn update_match(&mut self, elapsed: u128, audio: &mut Audio) -> Result<(), String> {
+        if self.role == Role::Join && self.connection.is_connected() && !self.sent_join {
+            self.connection.send(&NetMessage::Join { version: VERSION.to_string(), handling: Config::load()? });
+            self.sent_join = true;
+        }
+        for message in self.connection.receive() {
+            self.handle_message(message, elapsed)?;
+        }
+        let versus = match &mut self.versus {
+            Some(x) => x,
+            None => {
+                // Inputs from before the match started are not played
+                self.local_changes.clear();
+                return Ok(());
+            }
+        };
+
+        if versus.local_tick <= versus.tick + versus.input_delay {
+            let changes = std::mem::take(&mut self.local_changes);
+            if !changes.is_empty() {
+                versus.unsent.push((versus.local_tick, changes.clone()));
+            }
+            versus.pending[versus.local].push_back(changes);
+            versus.local_tick += 1;
+        }
+        // Inputs are sent straight away, ticks without any are only sent every so often
+        if !versus.unsent.is_empty() || versus.local_tick >= versus.sent_tick + versus.send_interval {
+            let changes = std::mem::take(&mut versus.unsent);
+            self.connection.send(&NetMessage::Inputs { until: versus.local_tick, changes });
+            versus.sent_tick = versus.local_tick;
+        }
+
+        while versus.outcome.is_none() && versus.pending.iter().all(|x| !x.is_empty()) {
+            let input_changes = versus.pending.iter_mut().map(|x| x.pop_front().unwrap()).collect::<Vec<_>>();
+            versus.outcome = versus_scene::step_players(&mut versus.players, &input_changes, versus.tick_length, &versus.attack_table, audio);
+            versus.tick += 1;
+            if versus.tick % HASH_INTERVAL == 0 {
+                let hash = versus.state_hash();
+                self.connection.send(&NetMessage::Hash { tick: versus.tick, hash });
+                versus.local_hashes.insert(versus.tick, hash);
+                versus.check_hashes()?;
+            }
+        }
+
+        match self.connection.closed() {
+            Some(reason) if versus.outcome.is_none() => Err(reason.to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl SceneTrait for OnlineScene {
+    fn handle_input(&mut self, input_events: Vec<TimedEvent>) {
+        for event in input_events {
+            if let Some(change) = input::read_timed_input_event(&event, &self.bindings) {
+                self.local_changes.push(change);
+            }
+        }
+    }
+
+    fn update(&mut self, elapsed: u128, audio: &mut Audio) -> SceneAction {
+        // Reset leaves the match, and pausing is not possible while someone else is playing
+        let leave = self.local_changes.iter().any(|x| matches!(x.input, GameInput::Reset) && x.pressed);
+        self.local_changes.retain(|x| !matches!(x.input, GameInput::Reset | GameInput::Pause));
+        if leave {
+            return SceneAction::Pop;
+        }
+
+        if self.ended.is_some() {
+            self.local_changes.clear();
+        } else if let Err(e) = self.update_match(elapsed, audio) {
+            eprintln!("Online versus ended: {}", e);
+            self.ended = Some(e);
+        }
+        SceneAction::Continue
+    }
+
+    fn render(&self, canvas: &mut WindowCanvas, assets: &mut Assets) -> Result<(), String> {
+        let status = match (&self.ended, &self.versus) {
+            (Some(reason), _) => Some(reason.as_str()),
+            (None, Some(_)) => None,
+            (None, None) if self.connection.is_connected() => Some("Starting"),
+            (None, None) => match self.role {
+                Role::Host => Some("Waiting for the other player"),
+                Role::Join => Some("Connecting"),
+            },
+        };
+
+        match &self.versus {
+            Some(versus) => {
+                let mut labels = [None, None];
+                if let Some(outcome) = versus.outcome {
+                    labels = outcome.labels();
+                } else if status.is_some() {
+                    labels = [status, status];
+                }
+                versus_scene::render_players(canvas, assets, &versus.gamemode, &versus.players, &versus.layout, &versus.input_overlay, &labels)
+            }
+            None => {
+                canvas.set_draw_color(Color::RGB(0, 0, 0));
+                canvas.clear();
+                let text = assets.get_game_assets(&self.net_config.gamemode, &None)?.popup_texture(status.unwrap_or_default())?;
+                text.set_alpha_mod(255);
+                let query = text.query();
+                let x = (crate::display::LOGICAL_WIDTH - query.width) as i32 / 2;
+                let y = (crate::display::LOGICAL_HEIGHT - query.height) as i32 / 2;
+                canvas.copy(text, None, Rect::new(x, y, query.width, query.height))
+            }
+        }
+    }
+}
+
+impl OnlineMatch {
+    fn new(gamemode: String, seed: u64, handling: [Config; 2], attack_table: AttackTable, tick_length: u128, local: usize) -> Result<Self, String> {
+        if tick_length == 0 {
+            return Err(String::from("The tick length must be more than 0"));
+        }
+        let [host_handling, join_handling] = handling;
+        let players = [
+            Player::new(Game::with_settings(&gamemode, seed, host_handling)?, HashMap::new())?,
+            Player::new(Game::with_settings(&gamemode, seed, join_handling)?, HashMap::new())?,
+        ];
+        let layout = crate::load_data_ron(Path::new(&format!("data/layouts/{}.ron", players[0].game.layout_name())))?;
+        let input_delay = INPUT_DELAY.div_ceil(tick_length) as u64;
+        // Nobody has inputs in the first few ticks, since nothing could have been sent for them
+        let empty_ticks = (0..input_delay).map(|_| Vec::new()).collect::<VecDeque<_>>();
+        Ok(Self {
+            gamemode,
+            attack_table,
+            tick_length,
+            players,
+            local,
+            layout,
+            input_overlay: crate::load_data_ron(Path::new("config/input_overlay.ron"))?,
+            pending: [empty_ticks.clone(), empty_ticks],
+            tick: 0,
+            input_delay,
+            send_interval: SEND_INTERVAL.div_ceil(tick_length).max(1) as u64,
+            local_tick: input_delay,
+            unsent: Vec::new(),
+            sent_tick: input_delay,
+            local_hashes: HashMap::new(),
+            remote_hashes: HashMap::new(),
+            outcome: None,
+        })
+    }
+
+    fn receive_inputs(&mut self, until: u64, changes: Vec<(u64, Vec<InputChange<GameInput>>)>) -> Result<(), String> {
+        let remote = &mut self.pending[1 - self.local];
+        let mut next = self.tick + remote.len() as u64;
+        for (tick, tick_changes) in changes {
+            if tick < next || tick >= until {
+                return Err(format!("Inputs for tick {} arrived out of order", tick));
+            }
+            remote.extend((next..tick).map(|_| Vec::new()));
+            remote.push_back(tick_changes);
+            next = tick + 1;
+        }
+        remote.extend((next..until).map(|_| Vec::new()));
+        Ok(())
+    }
+
+    fn state_hash(&self) -> u64 {
+        self.players[0].game.state_hash() ^ self.players[1].game.state_hash().rotate_left(32)
+    }
+
+    fn check_hashes(&mut self) -> Result<(), String> {
+        let ticks = self.remote_hashes.keys().filter(|x| self.local_hashes.contains_key(x)).copied().collect::<Vec<_>>();
+        for tick in ticks {
+            if self.local_hashes.remove(&tick) != self.remote_hashes.remove(&tick) {
+                return Err(format!("Desync at tick {}", tick));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_version(version: &str) -> Result<(), String> {
+    if version != VERSION {
+        return Err(format!("The other player has version {}, but this is version {}", version, VERSION));
+    }
+    Ok(())
+}
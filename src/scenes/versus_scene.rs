@@ -0,0 +1,238 @@
+use super::{SceneAction, SceneTrait};
+use crate::{
+    game::{Game, GameEvent, effects::Effects, garbage::AttackTable, layout::Layout, render::{self, InputOverlay}},
+    assets::Assets,
+    audio::Audio,
+    input::{self, GameInput, InputChange, TimedEvent},
+};
+
+use std::{collections::HashMap, path::Path};
+use enum_map::EnumMap;
+use serde::Deserialize;
+use sdl2::{event::Event, rect::Rect, render::WindowCanvas};
+
+#[derive(Deserialize)]
+pub(super) struct VersusConfig {
+    pub gamemode: String,
+    pub attack_table: AttackTable,
+}
+
+impl VersusConfig {
+    pub fn load() -> Result<Self, String> {
+        crate::load_data_ron(Path::new("config/versus.ron"))
+    }
+}
+
+/* Each player has their own keys, controller buttons are the same for both players */
+#[derive(Deserialize)]
+struct VersusControls {
+    player1: HashMap<String, GameInput>,
+    player2: HashMap<String, GameInput>,
+}
+
+/* One side of a versus match, also used by online versus */
+pub(super) struct Player {
+    pub bindings: HashMap<String, GameInput>, // Empty when the scene reads the player's inputs itself
+    pub game: Game,
+    effects: Effects,
+    pub inputs: EnumMap<GameInput, bool>,
+    press_times: EnumMap<GameInput, u32>,
+    pub input_changes: Vec<InputChange<GameInput>>,
+}
+
+impl Player {
+    pub fn new(game: Game, bindings: HashMap<String, GameInput>) -> Result<Self, String> {
+        Ok(Self {
+            bindings,
+            game,
+            effects: Effects::new(crate::load_data_ron(Path::new("config/effects.ron"))?),
+            inputs: EnumMap::default(),
+            press_times: EnumMap::default(),
+            input_changes: Vec::new(),
+        })
+    }
+
+    /* Returns the lines of garbage this player's clears attack with */
+    fn update(&mut self, input_changes: &[InputChange<GameInput>], elapsed: u128, attack_table: &AttackTable, audio: &mut Audio) -> u32 {
+        self.game.update(&mut self.inputs, &mut self.press_times, input_changes, elapsed);
+        let events = self.game.take_events();
+        let mut attack = 0;
+        for event in events.iter() {
+            if let GameEvent::LinesCleared(line_clear) = event {
+                self.effects.add_line_clear(line_clear);
+                attack += attack_table.attack(line_clear);
+            }
+        }
+        audio.play_game_events(&events);
+        self.effects.update(elapsed);
+        attack
+    }
+}
+
+/* How a match ended */
+#[derive(Clone, Copy)]
+pub(super) enum Outcome {
+    Winner(usize),
+    Draw, // Both players topped out in the same step
+}
+
+impl Outcome {
+    pub(super) fn labels(self) -> [Option<&'static str>; 2] {
+        match self {
+            Outcome::Winner(winner) => [0, 1].map(|i| Some(if winner == i { "WIN" } else { "LOSE" })),
+            Outcome::Draw => [Some("DRAW"), Some("DRAW")],
+        }
+    }
+}
+
+/* Two games side by side that send garbage to each other, until one of the players tops out */
+pub struct VersusScene {
+    config: VersusConfig,
+    players: [Player; 2],
+    layout: Layout,
+    input_overlay: InputOverlay,
+    controllers: Vec<u32>, // Controllers in the order their first button was pressed, the first one belongs to player 1
+    paused: bool,
+    outcome: Option<Outcome>,
+}
+
+impl VersusScene {
+    pub fn new() -> Result<Self, String> {
+        let config = VersusConfig::load()?;
+        let controls: VersusControls = crate::load_data(Path::new("config/versus_control_config.toml"))?;
+        let players = [
+            Player::new(Game::new(&config.gamemode)?, controls.player1)?,
+            Player::new(Game::new(&config.gamemode)?, controls.player2)?,
+        ];
+        let layout = crate::load_data_ron(Path::new(&format!("data/layouts/{}.ron", players[0].game.layout_name())))?;
+        Ok(Self {
+            config,
+            players,
+            layout,
+            input_overlay: crate::load_data_ron(Path::new("config/input_overlay.ron"))?,
+            controllers: Vec::new(),
+            paused: false,
+            outcome: None,
+        })
+    }
+
+    /* Which player a controller belongs to, controllers join when they first press a button */
+    fn controller_player(&mut self, which: u32, pressed: bool) -> Option<usize> {
+        if let Some(player) = self.controllers.iter().position(|x| *x == which) {
+            return Some(player);
+        }
+        if pressed && self.controllers.len() < self.players.len() {
+            self.controllers.push(which);
+            return Some(self.controllers.len() - 1);
+        }
+        None
+    }
+}
+
+impl SceneTrait for VersusScene {
+    fn handle_input(&mut self, input_events: Vec<TimedEvent>) {
+        for event in input_events {
+            let owner = match event.event {
+                Event::ControllerButtonDown { which, .. } => self.controller_player(which, true),
+                Event::ControllerButtonUp { which, .. } => self.controller_player(which, false),
+                _ => None,
+            };
+            let is_controller = matches!(event.event, Event::ControllerButtonDown { .. } | Event::ControllerButtonUp { .. });
+            for (i, player) in self.players.iter_mut().enumerate() {
+                if is_controller && owner != Some(i) {
+                    continue;
+                }
+                if let Some(change) = input::read_timed_input_event(&event, &player.bindings) {
+                    player.input_changes.push(change);
+                }
+            }
+        }
+    }
+
+    fn update(&mut self, elapsed: u128, audio: &mut Audio) -> SceneAction {
+        let input_changes = self.players.iter_mut().map(|x| std::mem::take(&mut x.input_changes)).collect::<Vec<_>>();
+        let pause_pressed = self.players.iter().zip(input_changes.iter())
+            .any(|(player, changes)| changes.iter().any(|x| matches!(x.input, GameInput::Pause) && x.pressed && !player.inputs[GameInput::Pause]));
+        if pause_pressed && self.outcome.is_none() {
+            self.paused = !self.paused;
+            audio.pause_music(self.paused);
+        }
+
+        if self.paused || self.outcome.is_some() {
+            // Keep track of held inputs without simulating, so they are correct when the game resumes
+            for (player, changes) in self.players.iter_mut().zip(input_changes.iter()) {
+                for change in changes.iter() {
+                    input::apply_input_change(&mut player.inputs, &mut player.press_times, change);
+                }
+            }
+        } else {
+            self.outcome = step_players(&mut self.players, &input_changes, elapsed, &self.config.attack_table, audio);
+        }
+
+        if self.players.iter().any(|x| x.inputs[GameInput::Reset]) {
+            let controllers = std::mem::take(&mut self.controllers);
+            *self = VersusScene::new().expect("Reset Error");
+            self.controllers = controllers;
+            audio.pause_music(false);
+        }
+        SceneAction::Continue
+    }
+
+    fn render(&self, canvas: &mut WindowCanvas, assets: &mut Assets) -> Result<(), String> {
+        let labels = self.outcome.map_or([None, None], Outcome::labels);
+        render_players(canvas, assets, &self.config.gamemode, &self.players, &self.layout, &self.input_overlay, &labels)
+    }
+}
+
+/* Simulate both games, then send each player's attack to the other. Returns the outcome once a player tops out */
+pub(super) fn step_players(players: &mut [Player; 2], input_changes: &[Vec<InputChange<GameInput>>], elapsed: u128, attack_table: &AttackTable, audio: &mut Audio) -> Option<Outcome> {
+    let mut attacks = [0; 2];
+    for (i, (player, changes)) in players.iter_mut().zip(input_changes.iter()).enumerate() {
+        attacks[i] = player.update(changes, elapsed, attack_table, audio);
+    }
+
+    // Attacks cancel the attacker's own incoming garbage first, what is left goes to the opponent
+    let delay = attack_table.delay as u128 * 1000;
+    for (i, attack) in attacks.iter().enumerate() {
+        let remaining = players[i].game.cancel_garbage(*attack);
+        players[1 - i].game.queue_garbage(remaining, delay);
+    }
+
+    match (players[0].game.is_over(), players[1].game.is_over()) {
+        (true, true) => Some(Outcome::Draw),
+        (true, false) => Some(Outcome::Winner(1)),
+        (false, true) => Some(Outcome::Winner(0)),
+        (false, false) => None,
+    }
+}
+
+/* Each game is drawn into its own texture, then they are shown side by side at half size with an optional label over each */
+pub(super) fn render_players(canvas: &mut WindowCanvas, assets: &mut Assets, gamemode_name: &str, players: &[Player], layout: &Layout, overlay: &InputOverlay, labels: &[Option<&str>]) -> Result<(), String> {
+    let (screen_width, screen_height) = (crate::display::LOGICAL_WIDTH, crate::display::LOGICAL_HEIGHT);
+    let (width, height) = (screen_width / 2, screen_height / 2);
+    canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 0, 0));
+    canvas.clear();
+
+    for (i, player) in players.iter().enumerate() {
+        let (game_assets, target) = assets.get_game_assets_and_target(gamemode_name, player.game.background(), i)?;
+        let mut result = Ok(());
+        canvas.with_texture_canvas(target, |target_canvas| {
+            result = render::render(target_canvas, &player.game, &player.effects, game_assets, layout, overlay, &player.inputs);
+        }).map_err(|e| e.to_string())?;
+        result?;
+
+        let x = (width * i as u32) as i32;
+        let y = ((screen_height - height) / 2) as i32;
+        canvas.copy(target, None, Rect::new(x, y, width, height))?;
+
+        if let Some(Some(label)) = labels.get(i) {
+            let text = game_assets.popup_texture(label)?;
+            text.set_alpha_mod(255);
+            let query = text.query();
+            let text_x = x + (width - query.width) as i32 / 2;
+            let text_y = y + (height - query.height) as i32 / 2;
+            canvas.copy(text, None, Rect::new(text_x, text_y, query.width, query.height))?;
+        }
+    }
+    Ok(())
+}
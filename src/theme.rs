@@ -0,0 +1,140 @@
+use crate::game::piece::PieceColor;
+
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+use sdl2::{image::LoadTexture, rect::Rect, render::{Texture, TextureCreator}, video::WindowContext};
+
+/* Sprites for each color, indexed by PieceColor then by neighbor bits */
+pub type ConnectedRegions = Vec<Vec<Rect>>;
+
+/* Describes the images, fonts and colors used to draw the game and menus */
+#[derive(Deserialize)]
+pub struct Theme {
+    #[serde(skip)]
+    pub name: String,
+    pub block_sheet: String,
+    pub tile_size: u32,
+    /* [column, row] of each color's sprite on the block sheet, in tiles */
+    pub block_sprites: HashMap<PieceColor, (u32, u32)>,
+    /* [column, row] of the first of a row of 16 sprites for each color, used to draw locked blocks joined to the rest of their piece.
+       Each sprite is for a combination of neighbors in the same piece: 1 up, 2 right, 4 down, 8 left */
+    pub connected_block_sprites: Option<HashMap<PieceColor, (u32, u32)>>,
+    pub frame: String,
+    pub menu_background: String,
+    pub menu_tile_overlay: String,
+    pub fonts: ThemeFonts,
+    pub colors: ThemeColors,
+}
+
+#[derive(Deserialize)]
+pub struct ThemeFonts {
+    pub stat: String,
+    pub label: String,
+    pub menu: String,
+}
+
+#[derive(Deserialize)]
+pub struct ThemeColors {
+    pub background: (u8, u8, u8),
+    pub menu_background: (u8, u8, u8),
+    pub stat: (u8, u8, u8),
+    pub stat_label: (u8, u8, u8),
+    pub next_level: (u8, u8, u8),
+    pub gamemode_name: (u8, u8, u8, u8),
+    pub ghost_outline: (u8, u8, u8),
+    pub hint: (u8, u8, u8),
+    pub menu_label: (u8, u8, u8),
+}
+
+impl Theme {
+    pub fn load(theme_name: &str) -> Result<Self, String> {
+        let mut theme: Theme = crate::load_data_ron(Path::new(&format!("data/themes/{}.ron", theme_name)))?;
+        theme.name = theme_name.to_string();
+        theme.validate()?;
+        Ok(theme)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.tile_size == 0 {
+            return Err(format!("Theme {} has invalid tile_size: 0", self.name));
+        }
+        for i in 0..PieceColor::ColorCount as usize {
+            let color = PieceColor::from_index(i);
+            if !self.block_sprites.contains_key(&color) {
+                return Err(format!("Theme {} is missing a block sprite for {:?}", self.name, color));
+            }
+        }
+        if let Some(connected) = &self.connected_block_sprites {
+            for i in 1..PieceColor::ColorCount as usize {
+                let color = PieceColor::from_index(i);
+                if !connected.contains_key(&color) {
+                    return Err(format!("Theme {} is missing a connected block sprite for {:?}", self.name, color));
+                }
+            }
+        }
+        for path in [&self.block_sheet, &self.frame, &self.menu_background, &self.menu_tile_overlay, &self.fonts.stat, &self.fonts.label, &self.fonts.menu] {
+            if !Path::new(path).is_file() {
+                return Err(format!("Theme {} refers to {}, but that file was not found", self.name, path));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load_texture<'a>(&self, texture_creator: &'a TextureCreator<WindowContext>, path: &str) -> Result<Texture<'a>, String> {
+        texture_creator.load_texture(path)
+            .map_err(|e| format!("Theme {} could not load {}: {}", self.name, path, e))
+    }
+
+    /* Find the region of the block sheet used by each color, indexed by PieceColor */
+    pub fn block_regions(&self, block_sheet: &Texture) -> Result<Vec<Rect>, String> {
+        let query = block_sheet.query();
+        if !query.width.is_multiple_of(self.tile_size) || !query.height.is_multiple_of(self.tile_size) {
+            return Err(format!(
+                "Theme {} block sheet {} is {}x{}, which is not a multiple of the tile size {}",
+                self.name, self.block_sheet, query.width, query.height, self.tile_size
+            ));
+        }
+
+        let mut regions = Vec::new();
+        for i in 0..PieceColor::ColorCount as usize {
+            let color = PieceColor::from_index(i);
+            regions.push(self.tile_region(block_sheet, color, self.block_sprites[&color])?);
+        }
+        Ok(regions)
+    }
+
+    /* Find the 16 connected regions for each color */
+    pub fn connected_block_regions(&self, block_sheet: &Texture) -> Result<Option<ConnectedRegions>, String> {
+        let connected = match &self.connected_block_sprites {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        // Empty blocks are never connected
+        let mut regions = vec![Vec::new()];
+        for i in 1..PieceColor::ColorCount as usize {
+            let color = PieceColor::from_index(i);
+            let (first_col, row) = connected[&color];
+            let mut color_regions = Vec::new();
+            for mask in 0..16 {
+                color_regions.push(self.tile_region(block_sheet, color, (first_col + mask, row))?);
+            }
+            regions.push(color_regions);
+        }
+        Ok(Some(regions))
+    }
+
+    fn tile_region(&self, block_sheet: &Texture, color: PieceColor, (col, row): (u32, u32)) -> Result<Rect, String> {
+        let query = block_sheet.query();
+        let x = col * self.tile_size;
+        let y = row * self.tile_size;
+        if x + self.tile_size > query.width || y + self.tile_size > query.height {
+            return Err(format!(
+                "Theme {} block sprite for {:?} at [{}, {}] is outside of the {}x{} block sheet",
+                self.name, color, col, row, query.width, query.height
+            ));
+        }
+        Ok(Rect::new(x as i32, y as i32, self.tile_size, self.tile_size))
+    }
+}